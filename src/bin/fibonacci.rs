@@ -0,0 +1,16 @@
+// Fibonacci numbers on their own, same as `untitled fib`.
+
+use clap::Parser;
+use std::process;
+use untitled::cli::{report, FibArgs};
+
+#[derive(Debug, Parser)]
+#[command(name = "fibonacci", version, about = "Print the nth Fibonacci number, counting 1, 1, 2, 3, ...")]
+struct Cli {
+    #[command(flatten)]
+    fib: FibArgs,
+}
+
+fn main() {
+    process::exit(report("fibonacci", Cli::parse().fib.run()));
+}
@@ -0,0 +1,25 @@
+// The guessing game on its own, same as `untitled guess`. The range can be
+// set in the config file too, see `untitled::config`, with `--config`
+// working like it does for `untitled`.
+
+use clap::Parser;
+use std::path::PathBuf;
+use std::process;
+use untitled::cli::{report, GuessArgs};
+use untitled::config::Config;
+
+#[derive(Debug, Parser)]
+#[command(name = "guessing_game", version, about = "Guess the number the computer picked")]
+struct Cli {
+    /// Read the settings from <FILE> instead of ~/.config/untitled/config.toml
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    game: GuessArgs,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let result = Config::load(cli.config.as_deref()).and_then(|config| cli.game.run(&config));
+    process::exit(report("guessing_game", result));
+}
@@ -0,0 +1,16 @@
+// The temperature converter on its own, same as `untitled convert-temp`.
+
+use clap::Parser;
+use std::process;
+use untitled::cli::{report, ConvertTempArgs};
+
+#[derive(Debug, Parser)]
+#[command(name = "temperature_converter", version, about = "Convert a temperature between Fahrenheit and Celsius")]
+struct Cli {
+    #[command(flatten)]
+    conversion: ConvertTempArgs,
+}
+
+fn main() {
+    process::exit(report("temperature_converter", Cli::parse().conversion.run()));
+}
@@ -1,15 +1,33 @@
-fn main() {
+use rand::Rng;
+use std::cmp::Ordering;
+use std::io;
+
+use crate::error::Error;
+
+// Numbers are picked from `low..=high`. The game stops when there is nothing
+// left to read, so it can be played from a file or a pipe as well.
+pub fn play(low: u32, high: u32) -> Result<(), Error> {
+    if low > high {
+        return Err(Error::OutOfRange {
+            what: "the lowest number",
+            value: low.to_string(),
+            allowed: format!("at most the highest number, {}", high),
+        });
+    }
     println!("Guess the number!");
-    let secret_number = rand::thread_rng().gen_range(1..=10);
+    let secret_number = rand::thread_rng().gen_range(low..=high);
     
     loop {
         println!("Please input your guess.");
 
         let mut guess = String::new();
 
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut guess)
-            .expect("Failed to read line");
+            .map_err(read_error)?;
+        if read == 0 {
+            return Ok(());
+        }
 
         let guess: u32 = match guess.trim().parse() {
             Ok(num) => num,
@@ -28,14 +46,17 @@ fn main() {
         }
     }
     println!("The game isn't over yet...");
-    let last_number: u32 = rand::thread_rng().gen_range(1..=10);
+    let last_number: u32 = rand::thread_rng().gen_range(low..=high);
     println!("Go ahead...\nGuess the last number.");
-    let mut user_guess = String::new();
-    
-    io::stdin()
-        .read_line(&mut user_guess)
-        .expect("Failed to read line");
     loop {
+        let mut user_guess = String::new();
+
+        let read = io::stdin()
+            .read_line(&mut user_guess)
+            .map_err(read_error)?;
+        if read == 0 {
+            return Ok(());
+        }
         let user_guess: u32 = match user_guess.trim().parse() {
             Ok(num) => num,
             Err(_) => {
@@ -51,4 +72,12 @@ fn main() {
         };
         break;
     }
+    Ok(())
+}
+
+fn read_error(source: io::Error) -> Error {
+    Error::Io {
+        action: "read the guess".to_string(),
+        source,
+    }
 }
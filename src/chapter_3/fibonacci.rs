@@ -1,8 +1,18 @@
+use crate::error::Error;
 
+// The biggest n whose number still fits in 64 bits.
+pub const FIBONACCI_MAX: usize = 93;
 
-pub fn fibonacci(n: usize) -> usize {
+pub fn fibonacci(n: usize) -> Result<usize, Error> {
+    if n > FIBONACCI_MAX {
+        return Err(Error::OutOfRange {
+            what: "n",
+            value: n.to_string(),
+            allowed: format!("at most {}", FIBONACCI_MAX),
+        });
+    }
     if n < 2 {
-        return 1;
+        return Ok(1);
     }
     
     let mut sequence = vec![0; n];
@@ -13,5 +23,5 @@ pub fn fibonacci(n: usize) -> usize {
     for i in 2..sequence.len() {
         sequence[i] = sequence[i - 1] + sequence[i - 2]; 
     }
-    sequence[n-1]
-}
\ No newline at end of file
+    Ok(sequence[n-1])
+}
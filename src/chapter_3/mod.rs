@@ -0,0 +1,2 @@
+pub mod fibonacci;
+pub mod temperature_converter;
@@ -1,9 +1,24 @@
+use crate::error::Error;
 
-pub fn convert_temperature(temp: &mut f64, temp_type: bool) -> () {
+// Absolute zero, nothing is colder.
+const MIN_FAHRENHEIT: f64 = -459.67;
+const MIN_CELSIUS: f64 = -273.15;
+
+pub fn convert_temperature(temp: &mut f64, temp_type: bool) -> Result<(), Error> {
+    let (min, scale) = if temp_type { (MIN_FAHRENHEIT, "°F") } else { (MIN_CELSIUS, "°C") };
+    // Infinity isn't a temperature either, and NaN isn't a number at all.
+    if *temp < min || !temp.is_finite() {
+        return Err(Error::OutOfRange {
+            what: "the temperature",
+            value: format!("{}{}", temp, scale),
+            allowed: format!("a finite number of at least {}{} (absolute zero)", min, scale),
+        });
+    }
     // F to C
     if temp_type {
         *temp = (*temp - 32.0) / 1.8
     } else { // C to F
         *temp = (*temp * 1.8) + 32.0
     }
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,226 @@
+// Who may change the directory.
+//
+// Users and their roles are listed in `users.json`, next to the directory:
+//
+// {"users": {"Sally": "admin", "Amir": "editor", "Bo": "viewer"}}
+//
+// - viewer: can look at everything but change nothing.
+// - editor: can also change the directory.
+// - admin: can also delete departments, import rosters and compact the
+//   journal, the things that are hard to take back.
+//
+// `undo` and `redo` need the role of the change they take back or make
+// again, so an editor can't redo a department an admin deleted and undid.
+// The program compacting the journal by itself when it ends is not checked:
+// the session's undo history ends with it anyway, and nothing in the
+// directory changes.
+//
+// Without a users file nobody is checked and everything is allowed, like
+// before roles existed. With one, the prompt starts with nobody logged in,
+// which counts as a viewer, and `login <user>` switches user. There are no
+// passwords, this is about stopping mistakes, not attackers.
+//
+// Every refused command is added to `denials.log`, one JSON record per line:
+//
+// {"time":"2024-03-04T09:15:00Z","user":"Bo","role":"viewer","command":"delete-department Sales"}
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::command::Command;
+use super::names::name_key;
+
+pub const DEFAULT_USERS_PATH: &str = "users.json";
+pub const DEFAULT_DENIAL_LOG_PATH: &str = "denials.log";
+
+// Each role can do everything the ones before it can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+
+    // The role needed to run `command`.
+    pub fn required_for(command: &Command) -> Role {
+        match command {
+            Command::DeleteDepartment { .. } | Command::Import { dry_run: false, .. } | Command::Compact => Role::Admin,
+            command if command.is_mutating() => Role::Editor,
+            _ => Role::Viewer,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct UsersFile {
+    users: BTreeMap<String, Role>,
+}
+
+#[derive(Serialize)]
+struct Denial<'a> {
+    time: String,
+    user: Option<&'a str>,
+    role: Role,
+    command: String,
+}
+
+// A user and their role, with the name spelled as in the users file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Default)]
+pub struct Access {
+    // By `name_key`. None when there is no users file and nobody is checked.
+    users: Option<BTreeMap<String, User>>,
+    user: Option<User>,
+    // Where refusals are logged, nowhere if None.
+    denial_log: Option<PathBuf>,
+}
+
+impl Access {
+    // Reads the users at `users_path`. A missing file means everything is
+    // allowed, a broken one is an error so a typo doesn't quietly let
+    // everybody in.
+    pub fn open(users_path: &Path, denial_log: &Path) -> io::Result<Access> {
+        let contents = match fs::read_to_string(users_path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Access::default()),
+            Err(error) => return Err(error),
+        };
+        let file: UsersFile = serde_json::from_str(&contents).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let users = file
+            .users
+            .into_iter()
+            .map(|(name, role)| (name_key(&name), User { name: name.trim().to_string(), role }))
+            .collect();
+        Ok(Access {
+            users: Some(users),
+            user: None,
+            denial_log: Some(denial_log.to_path_buf()),
+        })
+    }
+
+    pub fn user(&self) -> Option<&User> {
+        self.user.as_ref()
+    }
+
+    pub fn role(&self) -> Role {
+        match (&self.users, &self.user) {
+            (None, _) => Role::Admin,
+            (Some(_), Some(user)) => user.role,
+            (Some(_), None) => Role::Viewer,
+        }
+    }
+
+    // Switches to `name`. Without a users file there is nobody to log in as.
+    pub fn login(&mut self, name: &str) -> Result<&User, AccessError> {
+        let Some(users) = &self.users else {
+            return Err(AccessError::NoUsers);
+        };
+        let user = users.get(&name_key(name)).ok_or_else(|| AccessError::UnknownUser(name.trim().to_string()))?;
+        Ok(self.user.insert(user.clone()))
+    }
+
+    pub fn logout(&mut self) {
+        self.user = None;
+    }
+
+    // Whether the current user may run `command`. A refusal is logged
+    // before it is returned.
+    pub fn check(&self, command: &Command) -> Result<(), AccessError> {
+        let required = Role::required_for(command);
+        if self.role() >= required {
+            return Ok(());
+        }
+        let user = self.user.as_ref().map(|user| user.name.clone());
+        if let Err(error) = self.log_denial(command) {
+            eprintln!("Could not log the refused command: {}", error);
+        }
+        Err(AccessError::Denied {
+            user,
+            role: self.role(),
+            required,
+            command: command.to_string(),
+        })
+    }
+
+    fn log_denial(&self, command: &Command) -> io::Result<()> {
+        let Some(path) = &self.denial_log else {
+            return Ok(());
+        };
+        let denial = Denial {
+            time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            user: self.user.as_ref().map(|user| user.name.as_str()),
+            role: self.role(),
+            command: command.to_string(),
+        };
+        let mut line = serde_json::to_string(&denial).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        line.push('\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessError {
+    NoUsers,
+    UnknownUser(String),
+    // `user` is None when nobody is logged in.
+    Denied { user: Option<String>, role: Role, required: Role, command: String },
+}
+
+impl AccessError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AccessError::NoUsers => "no_users",
+            AccessError::UnknownUser(_) => "unknown_user",
+            AccessError::Denied { .. } => "permission_denied",
+        }
+    }
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessError::NoUsers => write!(f, "there is no {} file, everybody can do everything", DEFAULT_USERS_PATH),
+            AccessError::UnknownUser(name) => write!(f, "there is no user called {}", name),
+            AccessError::Denied { user: None, required, command, .. } => write!(
+                f,
+                "nobody is logged in, `{}` needs the {} role (use `login <user>`)",
+                command, required
+            ),
+            AccessError::Denied { user: Some(user), role, required, command } => write!(
+                f,
+                "{} has the {} role and `{}` needs the {} role",
+                user, role, command, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
@@ -0,0 +1,242 @@
+// Who changed the directory, when, and what it looked like before and after.
+//
+// Every command that changes the directory is added to `audit.log` once it
+// has worked, one JSON record per line:
+//
+// {"time":"2024-03-04T09:15:00Z","user":"Sally","command":"add Amir to Sales",
+//  "before":{"departments":{"Sales":{"members":["Bea"]}}},
+//  "after":{"departments":{"Sales":{"members":["Bea","Amir"]}},"employees":{"4":{"id":4,"name":"Amir"}}}}
+//
+// (on one line in the file). Only the departments and employees the command
+// touched are kept, as they were before and after it. One that is missing on
+// one side didn't exist at that point. `user` is null when nobody was logged
+// in, see `access`. Times are UTC, so they sort as text.
+//
+// The log is only ever added to. Undo and redo are changes like any other
+// and get their own records.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::directory::{is_id_reference, Department, Directory, Employee, EmployeeId, Field};
+use super::names::name_key;
+
+pub const DEFAULT_AUDIT_LOG_PATH: &str = "audit.log";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub time: String,
+    pub user: Option<String>,
+    pub command: String,
+    pub before: AuditState,
+    pub after: AuditState,
+}
+
+// Part of the directory at one point in time, by department name and by id.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditState {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub departments: BTreeMap<String, DepartmentState>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub employees: BTreeMap<EmployeeId, Employee>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepartmentState {
+    // By name, as they were called then.
+    pub members: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+impl DepartmentState {
+    fn of(department: &Department, directory: &Directory) -> Self {
+        DepartmentState {
+            members: department.members.iter().map(|id| directory.name_of(*id)).collect(),
+            parent: directory.parent(department).map(|parent| parent.name.clone()),
+        }
+    }
+}
+
+impl AuditRecord {
+    // A record of `command`, run by `user`, turning `before` into `after`.
+    pub fn new(user: Option<String>, command: &str, before: &Directory, after: &Directory) -> Self {
+        let mut record = AuditRecord {
+            time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            user,
+            command: command.to_string(),
+            before: AuditState::default(),
+            after: AuditState::default(),
+        };
+
+        let mut keys: Vec<String> = before.departments().chain(after.departments()).map(|department| name_key(&department.name)).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            let (old, new) = (before.department(&key), after.department(&key));
+            if old == new {
+                continue;
+            }
+            if let Some(old) = old {
+                record.before.departments.insert(old.name.clone(), DepartmentState::of(old, before));
+            }
+            if let Some(new) = new {
+                record.after.departments.insert(new.name.clone(), DepartmentState::of(new, after));
+            }
+        }
+
+        let mut ids: Vec<EmployeeId> = before.employees().chain(after.employees()).map(|employee| employee.id).collect();
+        ids.sort();
+        ids.dedup();
+        for id in ids {
+            let (old, new) = (before.employee(id), after.employee(id));
+            if old == new {
+                continue;
+            }
+            if let Some(old) = old {
+                record.before.employees.insert(id, old.clone());
+            }
+            if let Some(new) = new {
+                record.after.employees.insert(id, new.clone());
+            }
+        }
+        record
+    }
+
+    // Whether the change touched the department or person `subject` names,
+    // by name or, for people, by id.
+    pub fn mentions(&self, subject: &str) -> bool {
+        let key = name_key(subject);
+        let id: Option<EmployeeId> = if is_id_reference(subject) {
+            subject.trim().trim_start_matches('#').parse().ok()
+        } else {
+            None
+        };
+        [&self.before, &self.after].into_iter().any(|state| {
+            state.departments.keys().any(|name| name_key(name) == key)
+                || state.departments.values().any(|department| department.members.iter().any(|name| name_key(name) == key))
+                || state.employees.values().any(|employee| Some(employee.id) == id || name_key(&employee.name) == key)
+        })
+    }
+
+    // What changed, one line per department or person, for people to read.
+    pub fn changes(&self) -> Vec<String> {
+        let (before, after) = (&self.before, &self.after);
+        let members = |members: &[String]| if members.is_empty() { "nobody".to_string() } else { members.join(", ") };
+        let place = |parent: &Option<String>| parent.clone().unwrap_or_else(|| "the top level".to_string());
+        let mut lines = Vec::new();
+
+        let mut departments: Vec<&String> = before.departments.keys().chain(after.departments.keys()).collect();
+        departments.sort();
+        departments.dedup();
+        for name in departments {
+            match (before.departments.get(name), after.departments.get(name)) {
+                (Some(old), Some(new)) => {
+                    if old.members != new.members {
+                        lines.push(format!("{}: {} -> {}", name, members(&old.members), members(&new.members)));
+                    }
+                    if old.parent != new.parent {
+                        lines.push(format!("{}: moved from {} to {}", name, place(&old.parent), place(&new.parent)));
+                    }
+                }
+                (None, Some(new)) if new.members.is_empty() => lines.push(format!("{}: created", name)),
+                (None, Some(new)) => lines.push(format!("{}: created with {}", name, members(&new.members))),
+                (Some(_), None) => lines.push(format!("{}: gone", name)),
+                (None, None) => {}
+            }
+        }
+
+        let mut ids: Vec<&EmployeeId> = before.employees.keys().chain(after.employees.keys()).collect();
+        ids.sort();
+        ids.dedup();
+        for id in ids {
+            match (before.employees.get(id), after.employees.get(id)) {
+                (Some(old), Some(new)) => {
+                    for field in Field::ALL {
+                        let (old_value, new_value) = (field_value(old, field), field_value(new, field));
+                        if old_value != new_value {
+                            let show = |value: Option<String>| value.unwrap_or_else(|| "(none)".to_string());
+                            lines.push(format!(
+                                "{} (#{}): {} {} -> {}",
+                                new.name,
+                                id,
+                                field.as_str(),
+                                show(old_value),
+                                show(new_value)
+                            ));
+                        }
+                    }
+                }
+                (None, Some(new)) => lines.push(format!("{} (#{}): added to the directory", new.name, id)),
+                (Some(old), None) => lines.push(format!("{} (#{}): taken out of the directory", old.name, id)),
+                (None, None) => {}
+            }
+        }
+        lines
+    }
+
+    // Whether the change was made on `date` (YYYY-MM-DD) or later.
+    pub fn is_since(&self, date: &str) -> bool {
+        self.time.get(..date.len()).is_some_and(|day| day >= date)
+    }
+}
+
+fn field_value(employee: &Employee, field: Field) -> Option<String> {
+    match field {
+        Field::Name => Some(employee.name.clone()),
+        Field::Title => employee.title.clone(),
+        Field::Email => employee.email.clone(),
+        Field::StartDate => employee.start_date.clone(),
+        Field::Manager => employee.manager.map(|id| format!("#{}", id)),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: &Path) -> Self {
+        AuditLog { path: path.to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_string(record).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        line.push('\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        // One write for the whole line, like the journal.
+        file.write_all(line.as_bytes())?;
+        file.sync_data()
+    }
+
+    // Every record, oldest first. No log yet means no changes yet.
+    pub fn read(&self) -> io::Result<Vec<AuditRecord>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|error| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} is damaged at line {}: {}", self.path.display(), index + 1, error),
+                    )
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,770 @@
+// Parsing for the department prompt.
+//
+// A line is split into words on whitespace. Double or single quotes group
+// several words into one, so `add "Mary Ann" to "Human Resources"` adds
+// `Mary Ann` to `Human Resources`. Inside double quotes a backslash escapes
+// the next character. Keywords such as `to` and `from` only count when they
+// are not quoted, so `add Bob to "to"` does what it looks like.
+
+use std::fmt;
+
+use super::directory::{is_valid_date, Field};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    // Without a department the person is only added to the directory.
+    Add { name: String, department: Option<String> },
+    // With `recursive` the people in sub-departments are listed too.
+    List { department: String, recursive: bool, columns: Vec<Column> },
+    ListAll { columns: Vec<Column> },
+    Remove { name: String, department: String },
+    Move { name: String, from_department: String, to_department: String },
+    RenameDepartment { old_name: String, new_name: String },
+    DeleteDepartment { department: String },
+    // A `parent` of None means the top level.
+    CreateDepartment { name: String, parent: Option<String> },
+    Reparent { department: String, parent: Option<String> },
+    OrgChart,
+    Stats,
+    // `employee` is an id (`#3` or `3`) or a name, resolved against the directory later.
+    Set { employee: String, field: Field, value: String },
+    Unset { employee: String, field: Field },
+    Show { employee: String },
+    Assign { employee: String, manager: String },
+    Reports { employee: String },
+    Chain { employee: String },
+    Find { query: String },
+    Undo,
+    Redo,
+    History,
+    // `subject` is a department or a person, `since` a YYYY-MM-DD date.
+    Audit { subject: Option<String>, since: Option<String> },
+    Compact,
+    // With `dry_run` the roster is only checked and nothing is changed.
+    Import { path: String, dry_run: bool },
+    Export { path: String },
+    Login { user: String },
+    Help,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    // Nothing but whitespace was typed.
+    Empty,
+    UnterminatedQuote,
+    UnknownCommand(String),
+    // The command was recognised but its arguments don't fit `usage`.
+    BadArguments { usage: &'static str },
+    EmptyName { usage: &'static str },
+    UnknownField(String),
+    UnknownColumn(String),
+    BadDate(String),
+}
+
+// Something `list` and `list-all` can print for each person.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Id,
+    Name,
+    Title,
+    Email,
+    StartDate,
+    Manager,
+    Departments,
+}
+
+impl Column {
+    pub const ALL: [Column; 7] = [
+        Column::Id,
+        Column::Name,
+        Column::Title,
+        Column::Email,
+        Column::StartDate,
+        Column::Manager,
+        Column::Departments,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Name => "name",
+            Column::Title => "title",
+            Column::Email => "email",
+            Column::StartDate => "start-date",
+            Column::Manager => "manager",
+            Column::Departments => "departments",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Column> {
+        Column::ALL.into_iter().find(|column| column.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+impl ParseError {
+    // Like `DirectoryError::code`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::Empty => "empty",
+            ParseError::UnterminatedQuote => "unterminated_quote",
+            ParseError::UnknownCommand(_) => "unknown_command",
+            ParseError::BadArguments { .. } => "bad_arguments",
+            ParseError::EmptyName { .. } => "empty_name",
+            ParseError::UnknownField(_) => "unknown_field",
+            ParseError::UnknownColumn(_) => "unknown_column",
+            ParseError::BadDate(_) => "bad_date",
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "no command was given"),
+            ParseError::UnterminatedQuote => write!(f, "a quote was opened but never closed"),
+            ParseError::UnknownCommand(word) => {
+                write!(f, "unknown command `{}`, type `help` to see every command", word)
+            }
+            ParseError::BadArguments { usage } => write!(f, "usage: {}", usage),
+            ParseError::EmptyName { usage } => write!(f, "names can't be empty, usage: {}", usage),
+            ParseError::UnknownField(field) => {
+                let fields: Vec<&str> = Field::ALL.iter().map(|field| field.as_str()).collect();
+                write!(f, "unknown field `{}`, expected one of: {}", field, fields.join(", "))
+            }
+            ParseError::UnknownColumn(column) => {
+                let columns: Vec<&str> = Column::ALL.iter().map(|column| column.as_str()).collect();
+                write!(f, "unknown column `{}`, expected one of: {}", column, columns.join(", "))
+            }
+            ParseError::BadDate(date) => write!(f, "{} is not a date, expected YYYY-MM-DD", date),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub const ADD_USAGE: &str = "add <name> [to <department>]";
+pub const LIST_USAGE: &str = "list <department> [recursive] [with <column>,...]";
+pub const LIST_ALL_USAGE: &str = "list-all [with <column>,...]";
+pub const REMOVE_USAGE: &str = "remove <name> from <department>";
+pub const MOVE_USAGE: &str = "move <name> from <department> to <department>";
+pub const RENAME_DEPARTMENT_USAGE: &str = "rename-department <old name> <new name>";
+pub const DELETE_DEPARTMENT_USAGE: &str = "delete-department <department>";
+pub const CREATE_DEPARTMENT_USAGE: &str = "create-department <name> [under <department>]";
+pub const REPARENT_USAGE: &str = "reparent <department> [under <department>]";
+pub const ORG_CHART_USAGE: &str = "org-chart";
+pub const STATS_USAGE: &str = "stats";
+pub const SET_USAGE: &str = "set <employee> <field> <value>";
+pub const UNSET_USAGE: &str = "unset <employee> <field>";
+pub const SHOW_USAGE: &str = "show <employee>";
+pub const ASSIGN_USAGE: &str = "assign <employee> manager <employee>";
+pub const REPORTS_USAGE: &str = "reports <employee>";
+pub const CHAIN_USAGE: &str = "chain <employee>";
+pub const FIND_USAGE: &str = "find <query>";
+pub const UNDO_USAGE: &str = "undo";
+pub const REDO_USAGE: &str = "redo";
+pub const HISTORY_USAGE: &str = "history";
+pub const AUDIT_USAGE: &str = "audit [<department or name>] [since <YYYY-MM-DD>]";
+pub const COMPACT_USAGE: &str = "compact";
+pub const IMPORT_USAGE: &str = "import <file.csv> [dry-run]";
+pub const EXPORT_USAGE: &str = "export <file.csv>";
+pub const LOGIN_USAGE: &str = "login <user>";
+pub const HELP_USAGE: &str = "help";
+pub const QUIT_USAGE: &str = "quit";
+
+// Every command with a short description, in the order `help` prints them.
+pub const COMMAND_HELP: &[(&str, &str)] = &[
+    (ADD_USAGE, "add somebody to a department, creating it if needed"),
+    (LIST_USAGE, "list everybody in a department (and below it), optionally with extra columns"),
+    (LIST_ALL_USAGE, "list everybody in the company, optionally with extra columns"),
+    (REMOVE_USAGE, "take somebody out of a department"),
+    (MOVE_USAGE, "move somebody from one department to another"),
+    (RENAME_DEPARTMENT_USAGE, "rename a department"),
+    (DELETE_DEPARTMENT_USAGE, "delete a department and its memberships"),
+    (CREATE_DEPARTMENT_USAGE, "create an empty department, optionally inside another one"),
+    (REPARENT_USAGE, "move a department inside another one, or to the top without `under`"),
+    (ORG_CHART_USAGE, "show the departments as a tree with head counts"),
+    (STATS_USAGE, "show head counts per department and how they compare"),
+    (SET_USAGE, "set an employee's name, title, email, start-date or manager"),
+    (UNSET_USAGE, "clear one of an employee's fields"),
+    (SHOW_USAGE, "show everything known about an employee"),
+    (ASSIGN_USAGE, "make somebody report to another employee"),
+    (REPORTS_USAGE, "list everybody who reports to somebody, directly or not"),
+    (CHAIN_USAGE, "show somebody's managers all the way to the top"),
+    (FIND_USAGE, "search names, also with * and ? wildcards"),
+    (UNDO_USAGE, "take back the last change"),
+    (REDO_USAGE, "make the last undone change again"),
+    (HISTORY_USAGE, "list the recent changes that can be undone or redone"),
+    (AUDIT_USAGE, "show who changed what and when, for everything or one department or person"),
+    (COMPACT_USAGE, "fold the journal into a fresh save file (clears the history)"),
+    (IMPORT_USAGE, "add everybody in a name,department[,...] roster"),
+    (EXPORT_USAGE, "write the directory out as a roster"),
+    (LOGIN_USAGE, "switch to a user from users.json, which decides what you may change"),
+    (HELP_USAGE, "show this list"),
+    (QUIT_USAGE, "leave the program (also `exit` or `q`)"),
+];
+
+impl Command {
+    // Whether running this command can change the directory.
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            Command::Add { .. }
+            | Command::Remove { .. }
+            | Command::Move { .. }
+            | Command::RenameDepartment { .. }
+            | Command::DeleteDepartment { .. }
+            | Command::CreateDepartment { .. }
+            | Command::Reparent { .. }
+            | Command::Set { .. }
+            | Command::Unset { .. }
+            | Command::Assign { .. }
+            | Command::Undo
+            | Command::Redo => true,
+            Command::Import { dry_run, .. } => !dry_run,
+            Command::List { .. }
+            | Command::ListAll { .. }
+            | Command::Show { .. }
+            | Command::Find { .. }
+            | Command::Reports { .. }
+            | Command::Chain { .. }
+            | Command::OrgChart
+            | Command::Stats
+            | Command::History
+            | Command::Audit { .. }
+            | Command::Compact
+            | Command::Export { .. }
+            | Command::Login { .. }
+            | Command::Help
+            | Command::Quit => false,
+        }
+    }
+}
+
+// Writes the command back out the way it would be typed, quoting names where
+// needed, so that parsing the result gives the same command again.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let with_columns = |columns: &[Column]| {
+            if columns.is_empty() {
+                String::new()
+            } else {
+                let columns: Vec<&str> = columns.iter().map(|column| column.as_str()).collect();
+                format!(" with {}", columns.join(","))
+            }
+        };
+        match self {
+            Command::Add { name, department: None } => write!(f, "add {}", quote(name)),
+            Command::Add { name, department: Some(department) } => {
+                write!(f, "add {} to {}", quote(name), quote(department))
+            }
+            Command::List { department, recursive, columns } => write!(
+                f,
+                "list {}{}{}",
+                quote(department),
+                if *recursive { " recursive" } else { "" },
+                with_columns(columns)
+            ),
+            Command::ListAll { columns } => write!(f, "list-all{}", with_columns(columns)),
+            Command::Remove { name, department } => write!(f, "remove {} from {}", quote(name), quote(department)),
+            Command::Move { name, from_department, to_department } => write!(
+                f,
+                "move {} from {} to {}",
+                quote(name),
+                quote(from_department),
+                quote(to_department)
+            ),
+            Command::RenameDepartment { old_name, new_name } => {
+                write!(f, "rename-department {} {}", quote(old_name), quote(new_name))
+            }
+            Command::DeleteDepartment { department } => write!(f, "delete-department {}", quote(department)),
+            Command::CreateDepartment { name, parent: None } => write!(f, "create-department {}", quote(name)),
+            Command::CreateDepartment { name, parent: Some(parent) } => {
+                write!(f, "create-department {} under {}", quote(name), quote(parent))
+            }
+            Command::Reparent { department, parent: None } => write!(f, "reparent {}", quote(department)),
+            Command::Reparent { department, parent: Some(parent) } => {
+                write!(f, "reparent {} under {}", quote(department), quote(parent))
+            }
+            Command::OrgChart => write!(f, "org-chart"),
+            Command::Stats => write!(f, "stats"),
+            Command::Set { employee, field, value } => {
+                write!(f, "set {} {} {}", quote(employee), field.as_str(), quote(value))
+            }
+            Command::Unset { employee, field } => write!(f, "unset {} {}", quote(employee), field.as_str()),
+            Command::Show { employee } => write!(f, "show {}", quote(employee)),
+            Command::Assign { employee, manager } => {
+                write!(f, "assign {} manager {}", quote(employee), quote(manager))
+            }
+            Command::Reports { employee } => write!(f, "reports {}", quote(employee)),
+            Command::Chain { employee } => write!(f, "chain {}", quote(employee)),
+            Command::Find { query } => write!(f, "find {}", quote(query)),
+            Command::Undo => write!(f, "undo"),
+            Command::Redo => write!(f, "redo"),
+            Command::History => write!(f, "history"),
+            Command::Audit { subject, since } => {
+                write!(f, "audit")?;
+                if let Some(subject) = subject {
+                    write!(f, " {}", quote(subject))?;
+                }
+                if let Some(since) = since {
+                    write!(f, " since {}", since)?;
+                }
+                Ok(())
+            }
+            Command::Compact => write!(f, "compact"),
+            Command::Import { path, dry_run: false } => write!(f, "import {}", quote(path)),
+            Command::Import { path, dry_run: true } => write!(f, "import {} dry-run", quote(path)),
+            Command::Export { path } => write!(f, "export {}", quote(path)),
+            Command::Login { user } => write!(f, "login {}", quote(user)),
+            Command::Help => write!(f, "help"),
+            Command::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+// Quotes `text` if it wouldn't come back as a single plain word otherwise.
+pub fn quote(text: &str) -> String {
+    let is_plain = !text.is_empty()
+        && !text.chars().any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\' || c == ',')
+        && !["to", "from", "with", "under", "recursive", "since"].iter().any(|keyword| text.eq_ignore_ascii_case(keyword));
+    if is_plain {
+        return text.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        !self.quoted && self.text.eq_ignore_ascii_case(keyword)
+    }
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut text = String::new();
+        let mut quoted = false;
+        // A single word can mix quoted and unquoted parts, like a shell: ab"c d" is `abc d`.
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            if c == '"' || c == '\'' {
+                quoted = true;
+                let mut closed = false;
+                while let Some(inner) = chars.next() {
+                    if inner == c {
+                        closed = true;
+                        break;
+                    }
+                    if inner == '\\' && c == '"' {
+                        match chars.next() {
+                            Some(escaped) => text.push(escaped),
+                            None => break,
+                        }
+                    } else {
+                        text.push(inner);
+                    }
+                }
+                if !closed {
+                    return Err(ParseError::UnterminatedQuote);
+                }
+            } else {
+                text.push(c);
+            }
+        }
+        tokens.push(Token { text, quoted });
+    }
+    Ok(tokens)
+}
+
+pub fn parse_command(line: &str) -> Result<Command, ParseError> {
+    let tokens = tokenize(line)?;
+    let Some(first) = tokens.first() else {
+        return Err(ParseError::Empty);
+    };
+    let arguments = &tokens[1..];
+
+    let command = first.text.to_ascii_lowercase();
+    match command.as_str() {
+        "add" => {
+            match arguments {
+                [name] => Ok(Command::Add { name: name_from(name, ADD_USAGE)?, department: None }),
+                [name, to, department] => {
+                    expect_keyword(to, "to", ADD_USAGE)?;
+                    Ok(Command::Add {
+                        name: name_from(name, ADD_USAGE)?,
+                        department: Some(name_from(department, ADD_USAGE)?),
+                    })
+                }
+                _ => Err(ParseError::BadArguments { usage: ADD_USAGE }),
+            }
+        }
+        "list" => {
+            let Some((department, rest)) = arguments.split_first() else {
+                return Err(ParseError::BadArguments { usage: LIST_USAGE });
+            };
+            let (recursive, rest) = match rest.split_first() {
+                Some((recursive, rest)) if recursive.is_keyword("recursive") => (true, rest),
+                _ => (false, rest),
+            };
+            Ok(Command::List {
+                department: name_from(department, LIST_USAGE)?,
+                recursive,
+                columns: parse_columns(rest, LIST_USAGE)?,
+            })
+        }
+        "list-all" => Ok(Command::ListAll { columns: parse_columns(arguments, LIST_ALL_USAGE)? }),
+        "remove" => {
+            let [name, from, department] = arguments else {
+                return Err(ParseError::BadArguments { usage: REMOVE_USAGE });
+            };
+            expect_keyword(from, "from", REMOVE_USAGE)?;
+            Ok(Command::Remove {
+                name: name_from(name, REMOVE_USAGE)?,
+                department: name_from(department, REMOVE_USAGE)?,
+            })
+        }
+        "move" => {
+            let [name, from, from_department, to, to_department] = arguments else {
+                return Err(ParseError::BadArguments { usage: MOVE_USAGE });
+            };
+            expect_keyword(from, "from", MOVE_USAGE)?;
+            expect_keyword(to, "to", MOVE_USAGE)?;
+            Ok(Command::Move {
+                name: name_from(name, MOVE_USAGE)?,
+                from_department: name_from(from_department, MOVE_USAGE)?,
+                to_department: name_from(to_department, MOVE_USAGE)?,
+            })
+        }
+        "rename-department" => {
+            let [old_name, new_name] = arguments else {
+                return Err(ParseError::BadArguments { usage: RENAME_DEPARTMENT_USAGE });
+            };
+            Ok(Command::RenameDepartment {
+                old_name: name_from(old_name, RENAME_DEPARTMENT_USAGE)?,
+                new_name: name_from(new_name, RENAME_DEPARTMENT_USAGE)?,
+            })
+        }
+        "delete-department" => {
+            let [department] = arguments else {
+                return Err(ParseError::BadArguments { usage: DELETE_DEPARTMENT_USAGE });
+            };
+            Ok(Command::DeleteDepartment { department: name_from(department, DELETE_DEPARTMENT_USAGE)? })
+        }
+        "create-department" => {
+            let (name, parent) = parse_under(arguments, CREATE_DEPARTMENT_USAGE)?;
+            Ok(Command::CreateDepartment { name, parent })
+        }
+        "reparent" => {
+            let (department, parent) = parse_under(arguments, REPARENT_USAGE)?;
+            Ok(Command::Reparent { department, parent })
+        }
+        "org-chart" => {
+            expect_no_arguments(arguments, ORG_CHART_USAGE)?;
+            Ok(Command::OrgChart)
+        }
+        "stats" => {
+            expect_no_arguments(arguments, STATS_USAGE)?;
+            Ok(Command::Stats)
+        }
+        "set" => {
+            let [employee, field, value @ ..] = arguments else {
+                return Err(ParseError::BadArguments { usage: SET_USAGE });
+            };
+            // The value is everything after the field, so titles don't need quoting.
+            let value: Vec<&str> = value.iter().map(|token| token.text.as_str()).collect();
+            Ok(Command::Set {
+                employee: name_from(employee, SET_USAGE)?,
+                field: parse_field(field)?,
+                value: name_from(&Token { text: value.join(" "), quoted: true }, SET_USAGE)?,
+            })
+        }
+        "unset" => {
+            let [employee, field] = arguments else {
+                return Err(ParseError::BadArguments { usage: UNSET_USAGE });
+            };
+            Ok(Command::Unset {
+                employee: name_from(employee, UNSET_USAGE)?,
+                field: parse_field(field)?,
+            })
+        }
+        "show" => {
+            let [employee] = arguments else {
+                return Err(ParseError::BadArguments { usage: SHOW_USAGE });
+            };
+            Ok(Command::Show { employee: name_from(employee, SHOW_USAGE)? })
+        }
+        "assign" => {
+            let [employee, manager_keyword, manager] = arguments else {
+                return Err(ParseError::BadArguments { usage: ASSIGN_USAGE });
+            };
+            expect_keyword(manager_keyword, "manager", ASSIGN_USAGE)?;
+            Ok(Command::Assign {
+                employee: name_from(employee, ASSIGN_USAGE)?,
+                manager: name_from(manager, ASSIGN_USAGE)?,
+            })
+        }
+        "reports" => {
+            let [employee] = arguments else {
+                return Err(ParseError::BadArguments { usage: REPORTS_USAGE });
+            };
+            Ok(Command::Reports { employee: name_from(employee, REPORTS_USAGE)? })
+        }
+        "chain" => {
+            let [employee] = arguments else {
+                return Err(ParseError::BadArguments { usage: CHAIN_USAGE });
+            };
+            Ok(Command::Chain { employee: name_from(employee, CHAIN_USAGE)? })
+        }
+        "find" => {
+            if arguments.is_empty() {
+                return Err(ParseError::BadArguments { usage: FIND_USAGE });
+            }
+            // Like `set`, everything after the command is the query.
+            let query: Vec<&str> = arguments.iter().map(|token| token.text.as_str()).collect();
+            Ok(Command::Find { query: name_from(&Token { text: query.join(" "), quoted: true }, FIND_USAGE)? })
+        }
+        "undo" => {
+            expect_no_arguments(arguments, UNDO_USAGE)?;
+            Ok(Command::Undo)
+        }
+        "redo" => {
+            expect_no_arguments(arguments, REDO_USAGE)?;
+            Ok(Command::Redo)
+        }
+        "history" => {
+            expect_no_arguments(arguments, HISTORY_USAGE)?;
+            Ok(Command::History)
+        }
+        "audit" => {
+            // `since <date>` can only come last.
+            let (arguments, since) = match arguments {
+                [rest @ .., since, date] if since.is_keyword("since") => {
+                    if !is_valid_date(&date.text) {
+                        return Err(ParseError::BadDate(date.text.clone()));
+                    }
+                    (rest, Some(date.text.clone()))
+                }
+                arguments => (arguments, None),
+            };
+            let subject = match arguments {
+                [] => None,
+                [subject] => Some(name_from(subject, AUDIT_USAGE)?),
+                _ => return Err(ParseError::BadArguments { usage: AUDIT_USAGE }),
+            };
+            Ok(Command::Audit { subject, since })
+        }
+        "compact" => {
+            expect_no_arguments(arguments, COMPACT_USAGE)?;
+            Ok(Command::Compact)
+        }
+        "import" => match arguments {
+            [path] => Ok(Command::Import { path: name_from(path, IMPORT_USAGE)?, dry_run: false }),
+            [path, dry_run] if dry_run.is_keyword("dry-run") => {
+                Ok(Command::Import { path: name_from(path, IMPORT_USAGE)?, dry_run: true })
+            }
+            _ => Err(ParseError::BadArguments { usage: IMPORT_USAGE }),
+        },
+        "export" => {
+            let [path] = arguments else {
+                return Err(ParseError::BadArguments { usage: EXPORT_USAGE });
+            };
+            Ok(Command::Export { path: name_from(path, EXPORT_USAGE)? })
+        }
+        "login" => {
+            let [user] = arguments else {
+                return Err(ParseError::BadArguments { usage: LOGIN_USAGE });
+            };
+            Ok(Command::Login { user: name_from(user, LOGIN_USAGE)? })
+        }
+        "help" => {
+            expect_no_arguments(arguments, HELP_USAGE)?;
+            Ok(Command::Help)
+        }
+        "quit" | "exit" | "q" => {
+            expect_no_arguments(arguments, QUIT_USAGE)?;
+            Ok(Command::Quit)
+        }
+        _ => Err(ParseError::UnknownCommand(first.text.clone())),
+    }
+}
+
+fn expect_keyword(token: &Token, keyword: &str, usage: &'static str) -> Result<(), ParseError> {
+    if token.is_keyword(keyword) {
+        Ok(())
+    } else {
+        Err(ParseError::BadArguments { usage })
+    }
+}
+
+fn expect_no_arguments(arguments: &[Token], usage: &'static str) -> Result<(), ParseError> {
+    if arguments.is_empty() {
+        Ok(())
+    } else {
+        Err(ParseError::BadArguments { usage })
+    }
+}
+
+fn name_from(token: &Token, usage: &'static str) -> Result<String, ParseError> {
+    let name = token.text.trim();
+    if name.is_empty() {
+        Err(ParseError::EmptyName { usage })
+    } else {
+        Ok(name.to_string())
+    }
+}
+
+fn parse_field(token: &Token) -> Result<Field, ParseError> {
+    Field::from_name(&token.text).ok_or_else(|| ParseError::UnknownField(token.text.clone()))
+}
+
+// Parses `<department> [under <department>]`.
+fn parse_under(arguments: &[Token], usage: &'static str) -> Result<(String, Option<String>), ParseError> {
+    match arguments {
+        [department] => Ok((name_from(department, usage)?, None)),
+        [department, under, parent] => {
+            expect_keyword(under, "under", usage)?;
+            Ok((name_from(department, usage)?, Some(name_from(parent, usage)?)))
+        }
+        _ => Err(ParseError::BadArguments { usage }),
+    }
+}
+
+// Parses an optional `with id,title email` tail. Columns can be separated by
+// commas, spaces or both. Without a tail the list is empty and the command
+// uses its usual columns.
+fn parse_columns(tokens: &[Token], usage: &'static str) -> Result<Vec<Column>, ParseError> {
+    let Some((with, rest)) = tokens.split_first() else {
+        return Ok(Vec::new());
+    };
+    expect_keyword(with, "with", usage)?;
+
+    let mut columns = Vec::new();
+    for name in rest.iter().flat_map(|token| token.text.split(',')).filter(|name| !name.is_empty()) {
+        let column = Column::from_name(name.trim()).ok_or_else(|| ParseError::UnknownColumn(name.to_string()))?;
+        columns.push(column);
+    }
+    if columns.is_empty() {
+        return Err(ParseError::BadArguments { usage });
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(name: &str, department: &str) -> Command {
+        Command::Add { name: name.to_string(), department: Some(department.to_string()) }
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(parse_command(r#"add "Mary Ann" to "Human Resources""#), Ok(add("Mary Ann", "Human Resources")));
+        assert_eq!(parse_command("add 'Mary Ann' to Sales"), Ok(add("Mary Ann", "Sales")));
+        assert_eq!(parse_command(r#"add Mary" "Ann to Sales"#), Ok(add("Mary Ann", "Sales")));
+    }
+
+    #[test]
+    fn quoted_keywords_are_names() {
+        assert_eq!(parse_command(r#"add Bob to "to""#), Ok(add("Bob", "to")));
+        assert_eq!(parse_command("add Bob from Sales"), Err(ParseError::BadArguments { usage: ADD_USAGE }));
+    }
+
+    #[test]
+    fn backslashes_escape_inside_double_quotes_only() {
+        assert_eq!(parse_command(r#"add "Bob \"The Builder\"" to Sales"#), Ok(add(r#"Bob "The Builder""#, "Sales")));
+        assert_eq!(parse_command(r#"add "a\\b" to Sales"#), Ok(add(r"a\b", "Sales")));
+        assert_eq!(parse_command(r"add 'a\b' to Sales"), Ok(add(r"a\b", "Sales")));
+    }
+
+    #[test]
+    fn unterminated_quotes_are_errors() {
+        assert_eq!(parse_command(r#"add "Mary Ann to Sales"#), Err(ParseError::UnterminatedQuote));
+        assert_eq!(parse_command("add 'Mary to Sales"), Err(ParseError::UnterminatedQuote));
+        // The closing quote was escaped away.
+        assert_eq!(parse_command(r#"add "Mary\" to Sales"#), Err(ParseError::UnterminatedQuote));
+        assert_eq!(parse_command(r#"add "Mary\"#), Err(ParseError::UnterminatedQuote));
+    }
+
+    #[test]
+    fn empty_lines_and_names() {
+        assert_eq!(parse_command("   "), Err(ParseError::Empty));
+        assert!(matches!(parse_command(r#"add "" to Sales"#), Err(ParseError::EmptyName { .. })));
+    }
+
+    #[test]
+    fn quote_leaves_plain_words_alone() {
+        assert_eq!(quote("Sally"), "Sally");
+        assert_eq!(quote("Mary Ann"), r#""Mary Ann""#);
+        assert_eq!(quote("To"), r#""To""#);
+        assert_eq!(quote(r#"a"b\c"#), r#""a\"b\\c""#);
+        assert_eq!(quote(""), r#""""#);
+    }
+
+    #[test]
+    fn commands_print_as_lines_that_parse_back() {
+        // Names as the parser hands them out, so already trimmed.
+        let awkward = ["Sally", "Mary Ann", "to", "from", r#"Bob "B" \ Jr"#, "it's", "a,b", "#3"];
+        for name in awkward {
+            for department in awkward {
+                let commands = [
+                    add(name, department),
+                    Command::Add { name: name.to_string(), department: None },
+                    Command::Remove { name: name.to_string(), department: department.to_string() },
+                    Command::Move {
+                        name: name.to_string(),
+                        from_department: department.to_string(),
+                        to_department: name.to_string(),
+                    },
+                    Command::RenameDepartment { old_name: name.to_string(), new_name: department.to_string() },
+                    Command::CreateDepartment { name: name.to_string(), parent: Some(department.to_string()) },
+                    Command::Set { employee: name.to_string(), field: Field::Title, value: department.to_string() },
+                    Command::Find { query: name.to_string() },
+                ];
+                for command in commands {
+                    assert_eq!(parse_command(&command.to_string()), Ok(command.clone()), "{}", command);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn other_commands_print_back_too() {
+        let lines = [
+            "list Sales recursive with id,name,manager",
+            "list-all",
+            "audit Sales since 2024-03-04",
+            "import roster.csv dry-run",
+            "unset Sally email",
+            "undo",
+            "quit",
+        ];
+        for line in lines {
+            let command = parse_command(line).unwrap();
+            assert_eq!(parse_command(&command.to_string()), Ok(command.clone()), "{}", line);
+        }
+    }
+}
@@ -2,17 +2,30 @@
 // sorted, the value in the middle position) and mode (the value that
 // occurs most often; a hash map will be helpful here) of the list.
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MedianResult {
     Int(i32),
     Float(f32),
 }
 
+impl fmt::Display for MedianResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MedianResult::Int(int_num) => write!(f, "{}", int_num),
+            MedianResult::Float(floating_num) => write!(f, "{}", floating_num),
+        }
+    }
+}
 
-pub fn find_median(integers_list: &[i32; 6]) -> MedianResult {
-    let integers_vector: Vec<&i32> = integers_list.iter().collect();
-    if integers_vector.len() % 2 == 1 {
-        let median_index = ((integers_vector.len() / 2) as f64).ceil() as usize;
-        MedianResult::Int(integers_vector[median_index].clone())  
+// An empty list has no middle, so it is an error.
+pub fn find_median(integers_list: &[i32]) -> Result<MedianResult, Error> {
+    let mut integers_vector: Vec<i32> = integers_list.to_vec();
+    integers_vector.sort_unstable();
+    if integers_vector.is_empty() {
+        Err(Error::EmptyInput("numbers"))
+    } else if integers_vector.len() % 2 == 1 {
+        let median_index = integers_vector.len() / 2;
+        Ok(MedianResult::Int(integers_vector[median_index]))
     } else {
         let median_low_index= integers_vector.len() / 2 - 1;
         let median_high_index = integers_vector.len() / 2;
@@ -20,33 +33,99 @@ pub fn find_median(integers_list: &[i32; 6]) -> MedianResult {
         let median_low_value = integers_vector[median_low_index];
         let median_high_value = integers_vector[median_high_index];
 
-        MedianResult::Float((*median_low_value as f32 + *median_high_value as f32) / 2.0)
+        Ok(MedianResult::Float((median_low_value as f32 + median_high_value as f32) / 2.0))
     }
 }
 
 use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
 
-pub fn _find_mode(integers_list: &[i32; 6]) -> i32 {
+use super::access::AccessError;
+use super::audit::{AuditLog, AuditRecord};
+use super::command::{parse_command, Column, Command, ParseError, COMMAND_HELP};
+use super::directory::{is_id_reference, Department, Directory, DirectoryError, Employee, EmployeeId, Field};
+use super::history::History;
+use super::names::display_name;
+use super::persistence::write_atomically;
+use super::roster::{export_roster, plan_import};
+use super::search::find;
+use super::output::{render_error, Output, OutputFormat};
+use super::repl::interrupted;
+use super::session::Session;
+use super::store::DirectoryStore;
+use crate::error::Error;
+
+// When several values are just as common the smallest one wins, so the
+// answer doesn't depend on the order of the hash map. An empty list has no
+// mode, so it is an error.
+pub fn _find_mode(integers_list: &[i32]) -> Result<i32, Error> {
     let mut hash_map: HashMap<i32, i32> = HashMap::new();
 
     for num in integers_list {
-        let mut count = hash_map.entry(*num).or_insert(0);
+        let count = hash_map.entry(*num).or_insert(0);
         *count += 1;
     }
-    let mut mode = 0;
+    let mut mode: Option<(i32, i32)> = None;
     for (key, value) in hash_map {
-        if value > mode {
-            mode = key;
+        let is_better = match mode {
+            None => true,
+            Some((mode_key, mode_count)) => value > mode_count || (value == mode_count && key < mode_key),
+        };
+        if is_better {
+            mode = Some((key, value));
         }
     }
-    mode
+    mode.map(|(key, _)| key).ok_or(Error::EmptyInput("numbers"))
+}
+
+// What words starting with a vowel get on the end. Everybody agrees about
+// the other words.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    #[default]
+    Yay,
+    Way,
+    Hay,
+    Ay,
+}
+
+impl Dialect {
+    pub const ALL: [Dialect; 4] = [Dialect::Yay, Dialect::Way, Dialect::Hay, Dialect::Ay];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dialect::Yay => "yay",
+            Dialect::Way => "way",
+            Dialect::Hay => "hay",
+            Dialect::Ay => "ay",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        Dialect::ALL.into_iter().find(|dialect| dialect.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+// Converts every word in place, with "yay" after vowels. No words at all is
+// an error.
+pub fn pig_latin_conversion(strings: &mut Vec<String>) -> Result<&mut Vec<String>, Error> {
+    pig_latin_in_dialect(strings, Dialect::Yay)
 }
 
-pub fn pig_latin_conversion(strings: &mut Vec<String>) -> &mut Vec<String> {
+pub fn pig_latin_in_dialect(strings: &mut Vec<String>, dialect: Dialect) -> Result<&mut Vec<String>, Error> {
+    if strings.is_empty() {
+        return Err(Error::EmptyInput("words"));
+    }
     let vowels = ['a', 'e', 'i', 'o', 'u'];
     for word in strings.iter_mut() {
         if word.starts_with(vowels) {
-            word.push_str("yay");
+            word.push_str(dialect.as_str());
         } else if word.is_ascii() {
             let mut consonants = String::new();
             let mut punctuation = String::new();
@@ -74,53 +153,1015 @@ pub fn pig_latin_conversion(strings: &mut Vec<String>) -> &mut Vec<String> {
             println!("Ngl I dont think {} is in english", &word);
         }
     }
-    strings
+    Ok(strings)
+}
+
+// What running a command did, so the caller knows whether the directory
+// changed or the prompt should stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Unchanged,
+    Changed,
+    Quit,
+}
+
+// A line that couldn't be run, either because it didn't parse, because the
+// user isn't allowed to run it or because the directory refused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Parse(ParseError),
+    Access(AccessError),
+    Directory(DirectoryError),
+}
+
+impl CommandError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CommandError::Parse(error) => error.code(),
+            CommandError::Access(error) => error.code(),
+            CommandError::Directory(error) => error.code(),
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Parse(error) => write!(f, "{}", error),
+            CommandError::Access(error) => write!(f, "{}", error),
+            CommandError::Directory(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+// Runs one line typed at the department prompt and prints the result in
+// the session's output format.
+pub fn handle_command(command: &str, session: &mut Session) -> Outcome {
+    match run_line(command, session) {
+        Ok((outcome, output)) => {
+            for line in output.render(session.format) {
+                println!("{}", line);
+            }
+            outcome
+        }
+        Err(error) => {
+            match session.format {
+                OutputFormat::Text => println!("Error: {}", error),
+                format => println!("{}", render_error(&error, None, format)),
+            }
+            Outcome::Unchanged
+        }
+    }
+}
+
+// Parses and runs one line, returning what it did and what it wants to show
+// without printing anything.
+pub fn run_line(line: &str, session: &mut Session) -> Result<(Outcome, Output), CommandError> {
+    let command = match parse_command(line) {
+        Ok(command) => command,
+        // An empty line does nothing, at the prompt it just shows the prompt again.
+        Err(ParseError::Empty) => return Ok((Outcome::Unchanged, Output::Lines(Vec::new()))),
+        Err(error) => return Err(CommandError::Parse(error)),
+    };
+    run_command(command, session)
+}
+
+// Runs a command for whoever is logged in: checks they may, runs it and
+// audits the change. Also what the HTTP API calls, with commands it builds
+// itself instead of parsing them.
+pub fn run_command(command: Command, session: &mut Session) -> Result<(Outcome, Output), CommandError> {
+    if command == Command::Quit {
+        return Ok((Outcome::Quit, Output::Lines(Vec::new())));
+    }
+    if let Command::Login { user } = &command {
+        let user = session.access.login(user).map_err(CommandError::Access)?;
+        let message = format!("Logged in as {} ({})", user.name, user.role);
+        return Ok((Outcome::Unchanged, Output::Lines(vec![message])));
+    }
+    let replayed = replayed_command(&command, &session.history);
+    session.access.check(replayed.as_ref().unwrap_or(&command)).map_err(CommandError::Access)?;
+
+    let is_mutating = command.is_mutating();
+    // Compacting is what gets the changes out of the journal.
+    let is_compact = command == Command::Compact;
+    let description = command.to_string();
+    let before = is_mutating.then(|| session.directory.clone());
+    let output = execute(command, session).map_err(CommandError::Directory)?;
+    // The change has been made, a log that can't be written only gets a warning.
+    if let (Some(before), Some(audit)) = (before, &session.audit) {
+        let user = session.access.user().map(|user| user.name.clone());
+        let record = AuditRecord::new(user, &description, &before, &session.directory);
+        if let Err(error) = audit.append(&record) {
+            eprintln!("Could not write to the audit log: {}", error);
+        }
+    }
+    if is_mutating && !is_compact {
+        session.dirty = true;
+    }
+    let outcome = if is_mutating { Outcome::Changed } else { Outcome::Unchanged };
+    Ok((outcome, output))
+}
+
+// Runs a parsed command against the session and returns what it wants to
+// show, without printing anything. Changes are written to the journal, if
+// the session has one, before they are applied.
+pub fn execute(command: Command, session: &mut Session) -> Result<Output, DirectoryError> {
+    // Import journals the steps the roster turned into, not the command.
+    if let Command::Import { path, dry_run } = &command {
+        return handle_import_command(path, *dry_run, session).map(Output::Lines);
+    }
+
+    if command.is_mutating()
+        && let Some(storage) = &mut session.storage
+    {
+        storage
+            .log(&command.to_string(), &[])
+            .map_err(|error| DirectoryError::Storage(error.to_string()))?;
+    }
+
+    // Undo and redo manage the history themselves, everything else that
+    // changes the directory is recorded so it can be undone.
+    let before = match command {
+        Command::Undo | Command::Redo => None,
+        _ if command.is_mutating() => Some(session.directory.clone()),
+        _ => None,
+    };
+    let description = command.to_string();
+
+    let output = match command {
+        Command::Undo => Output::Lines(handle_undo_command(session)?),
+        Command::Redo => Output::Lines(handle_redo_command(session)?),
+        Command::History => Output::Lines(handle_history_command(&session.history)),
+        Command::Audit { subject, since } => {
+            handle_audit_command(subject.as_deref(), since.as_deref(), session.audit.as_ref())?
+        }
+        Command::Compact => Output::Lines(handle_compact_command(session)?),
+        Command::Export { path } => Output::Lines(handle_export_command(&path, &session.directory)?),
+        Command::Help => Output::Lines(handle_help()),
+        Command::Quit => Output::Lines(Vec::new()),
+        command => apply(command, &mut session.directory)?,
+    };
+
+    if let Some(before) = before {
+        session.history.record(description, before, session.directory.clone());
+    }
+    Ok(output)
+}
+
+// Runs a command that only needs the directory itself. These are also the
+// commands an import can be made of.
+fn apply(command: Command, directory: &mut Directory) -> Result<Output, DirectoryError> {
+    // Listings have records to go with their text, everything else is just lines.
+    let lines = match command {
+        Command::List { department, recursive, columns } => {
+            return Ok(handle_list_department(&department, recursive, &columns, directory));
+        }
+        Command::ListAll { columns } => return Ok(handle_list_company(&columns, directory)),
+        Command::Find { query } => return Ok(handle_find_command(&query, directory)),
+        Command::OrgChart => return Ok(handle_org_chart(directory)),
+        Command::Stats => return Ok(handle_stats_command(directory)),
+        Command::Add { name, department } => handle_add_command(&name, department.as_deref(), directory),
+        Command::Remove { name, department } => handle_remove_command(&name, &department, directory),
+        Command::Move { name, from_department, to_department } => {
+            handle_move_command(&name, &from_department, &to_department, directory)
+        }
+        Command::RenameDepartment { old_name, new_name } => handle_rename_department(&old_name, &new_name, directory),
+        Command::DeleteDepartment { department } => handle_delete_department(&department, directory),
+        Command::CreateDepartment { name, parent } => handle_create_department(&name, parent.as_deref(), directory),
+        Command::Reparent { department, parent } => handle_reparent_command(&department, parent.as_deref(), directory),
+        Command::Set { employee, field, value } => handle_set_command(&employee, field, Some(&value), directory),
+        Command::Unset { employee, field } => handle_set_command(&employee, field, None, directory),
+        Command::Show { employee } => handle_show_command(&employee, directory),
+        Command::Assign { employee, manager } => handle_assign_command(&employee, &manager, directory),
+        Command::Reports { employee } => handle_reports_command(&employee, directory),
+        Command::Chain { employee } => handle_chain_command(&employee, directory),
+        command => Err(DirectoryError::NotAStep(command.to_string())),
+    };
+    lines.map(Output::Lines)
+}
+
+// Replays a journal record made of several steps as the single operation
+// it was when it first ran.
+pub fn replay_steps(description: &str, steps: &[String], session: &mut Session) {
+    let before = session.directory.clone();
+    for step in steps {
+        if let Ok(command) = parse_command(step) {
+            let _ = apply(command, &mut session.directory);
+        }
+    }
+    session.history.record(description.to_string(), before, session.directory.clone());
+}
+
+fn handle_help() -> Vec<String> {
+    let mut lines = vec!["Commands:".to_string()];
+    for (usage, description) in COMMAND_HELP {
+        lines.push(format!("  {:<48} {}", usage, description));
+    }
+    lines.push("Names with spaces can be quoted: add \"Mary Ann\" to \"Human Resources\"".to_string());
+    lines.push("Employees can be given by name or by id (#3). Columns for `with`: id, name, title,".to_string());
+    lines.push("email, start-date, manager, departments.".to_string());
+    lines
+}
+
+// The change `undo` or `redo` would take back or make again, which is what
+// access is checked against. None for other commands, or when there is
+// nothing to undo or redo.
+fn replayed_command(command: &Command, history: &History) -> Option<Command> {
+    let operation = match command {
+        Command::Undo => history.done().last()?,
+        Command::Redo => history.undone().last()?,
+        _ => return None,
+    };
+    parse_command(&operation.description).ok()
+}
+
+fn handle_undo_command(session: &mut Session) -> Result<Vec<String>, DirectoryError> {
+    match session.history.undo(&mut session.directory) {
+        Some(operation) => Ok(vec![format!("Undid: {}", operation.description)]),
+        None => Err(DirectoryError::NothingToUndo),
+    }
+}
+
+fn handle_redo_command(session: &mut Session) -> Result<Vec<String>, DirectoryError> {
+    match session.history.redo(&mut session.directory) {
+        Some(operation) => Ok(vec![format!("Redid: {}", operation.description)]),
+        None => Err(DirectoryError::NothingToRedo),
+    }
+}
+
+fn handle_history_command(history: &History) -> Vec<String> {
+    if history.done().is_empty() && history.undone().is_empty() {
+        return vec!["Nothing has been changed yet.".to_string()];
+    }
+    let mut lines = Vec::new();
+    for (index, operation) in history.done().iter().enumerate() {
+        lines.push(format!("{}. {}", index + 1, operation.description));
+    }
+    // Undone operations are listed in the order `redo` would bring them back.
+    let done = history.done().len();
+    for (index, operation) in history.undone().iter().rev().enumerate() {
+        lines.push(format!("{}. {} (undone)", done + index + 1, operation.description));
+    }
+    lines
+}
+
+// The audit records about `subject`, made on `since` or later, oldest first.
+fn handle_audit_command(subject: Option<&str>, since: Option<&str>, audit: Option<&AuditLog>) -> Result<Output, DirectoryError> {
+    let Some(audit) = audit else {
+        return Ok(Output::Lines(vec!["Changes are not being audited in this session.".to_string()]));
+    };
+    let records = audit.read().map_err(|error| DirectoryError::File {
+        path: audit.path().display().to_string(),
+        error: error.to_string(),
+    })?;
+    let records: Vec<AuditRecord> = records
+        .into_iter()
+        .filter(|record| subject.is_none_or(|subject| record.mentions(subject)))
+        .filter(|record| since.is_none_or(|since| record.is_since(since)))
+        .collect();
+
+    let mut lines = Vec::new();
+    for record in &records {
+        let user = record.user.as_deref().unwrap_or("nobody");
+        lines.push(format!("{} {}: {}", record.time, user, record.command));
+        lines.extend(record.changes().into_iter().map(|change| format!("    {}", change)));
+    }
+    if records.is_empty() {
+        lines.push("No changes found.".to_string());
+    }
+    let columns = ["time", "user", "command", "before", "after"].map(String::from).to_vec();
+    let rows = records
+        .iter()
+        .map(|record| {
+            vec![
+                json!(record.time),
+                json!(record.user),
+                json!(record.command),
+                json!(record.before),
+                json!(record.after),
+            ]
+        })
+        .collect();
+    Ok(Output::Listing { text: lines, columns, rows })
+}
+
+fn handle_compact_command(session: &mut Session) -> Result<Vec<String>, DirectoryError> {
+    let folded = session.compact().map_err(|error| DirectoryError::Storage(error.to_string()))?;
+    let Some(storage) = &session.storage else {
+        return Ok(vec!["This directory isn't saved to disk, there is nothing to compact.".to_string()]);
+    };
+    Ok(vec![format!(
+        "Folded {} journal records into {}",
+        folded,
+        storage.snapshot_path().display()
+    )])
+}
+
+// Adds `name` to `department`, keeping everybody who is already there.
+// The same person can belong to several departments, but only once to each.
+// Without a department the person is only added to the store.
+// Names that match an existing person or department apart from case or
+// spacing mean that one, with a warning in case it was a different one.
+fn handle_add_command(name: &str, department: Option<&str>, store: &mut impl DirectoryStore) -> Result<Vec<String>, DirectoryError> {
+    let (id, is_new_employee) = store.resolve_or_create(name)?;
+    let mut warnings = Vec::new();
+    if !is_new_employee && !is_id_reference(name) {
+        warnings.extend(spelling_warning(name, &store.name_of(id), "employee"));
+    }
+    let name = store.name_of(id);
+    let Some(department) = department else {
+        if !is_new_employee {
+            return Err(DirectoryError::AlreadyInDirectory(name));
+        }
+        return Ok(vec![format!("{} is a new employee with id #{}", name, id)]);
+    };
+    if let Some(existing) = store.department(department) {
+        warnings.extend(spelling_warning(department, &existing.name, "department"));
+    }
+    let is_new_department = store.add_member(department, id)?;
+    let department = store.department(department).expect("just added to it");
+
+    let mut lines = Vec::new();
+    if is_new_employee {
+        lines.push(format!("{} is a new employee with id #{}", name, id));
+    }
+    if is_new_department {
+        lines.push(format!("Created the {} department and added {} to it", department.name, name));
+    } else {
+        lines.push(format!(
+            "Added {} to the {} department ({} now)",
+            name,
+            department.name,
+            people(department.members.len())
+        ));
+    }
+
+    let other_departments: Vec<String> = store
+        .departments_of(id)
+        .into_iter()
+        .filter(|other| *other != department.name)
+        .collect();
+    if !other_departments.is_empty() {
+        lines.push(format!("{} is also in: {}", name, other_departments.join(", ")));
+    }
+    lines.extend(warnings);
+    Ok(lines)
+}
+
+// Points out that `typed` was taken to mean something already spelled
+// `existing`, unless they are spelled the same.
+fn spelling_warning(typed: &str, existing: &str, kind: &str) -> Option<String> {
+    (display_name(typed) != existing)
+        .then(|| format!("Warning: {} was taken to mean the existing {} {}", typed, kind, existing))
+}
+
+// Reads a roster and adds everybody in it. The whole roster is tried on a
+// copy of the directory first, which is all a dry run does. A row that fails
+// is left out entirely and reported by line, the rest are still imported, as
+// one operation that a single `undo` takes back.
+fn handle_import_command(path: &str, dry_run: bool, session: &mut Session) -> Result<Vec<String>, DirectoryError> {
+    let text = fs::read_to_string(path).map_err(|error| DirectoryError::File {
+        path: path.to_string(),
+        error: error.to_string(),
+    })?;
+    let plan = plan_import(&text);
+
+    let mut directory = session.directory.clone();
+    let mut applied = Vec::new();
+    let mut already_there = 0;
+    // Rows left out altogether, and rows imported without their manager.
+    let mut errors = plan.errors;
+    let mut without_manager = Vec::new();
+    let mut steps = plan.steps.into_iter().peekable();
+    while let Some((line, step)) = steps.next() {
+        // Nothing has been applied to the session yet, so stopping here leaves it as it was.
+        if interrupted() {
+            return Err(DirectoryError::Interrupted);
+        }
+        // Steps for the same row come one after another and stand or fall together.
+        let mut row = vec![step];
+        while let Some((_, step)) = steps.next_if(|(next_line, _)| *next_line == line) {
+            row.push(step);
+        }
+
+        // Managers are set in a second pass, so failing there only loses the manager.
+        let is_manager_step = matches!(row[0], Command::Set { field: Field::Manager, .. });
+        // A row that was left out has nobody to give a manager to.
+        if is_manager_step && errors.iter().any(|(error_line, _)| *error_line == line) {
+            continue;
+        }
+        let row_start = directory.clone();
+        let applied_before = applied.len();
+        for step in row {
+            let description = step.to_string();
+            match apply(step, &mut directory) {
+                Ok(_) => applied.push(description),
+                Err(DirectoryError::AlreadyInDepartment { .. } | DirectoryError::AlreadyInDirectory(_)) => {
+                    already_there += 1
+                }
+                Err(error) if is_manager_step => {
+                    without_manager.push((line, error.to_string()));
+                    directory = row_start;
+                    applied.truncate(applied_before);
+                    break;
+                }
+                Err(error) => {
+                    errors.push((line, error.to_string()));
+                    directory = row_start;
+                    applied.truncate(applied_before);
+                    break;
+                }
+            }
+        }
+    }
+    errors.sort_by_key(|(line, _)| *line);
+    without_manager.sort_by_key(|(line, _)| *line);
+
+    let before = &session.directory;
+    let new_employees = directory.employees().filter(|employee| before.employee(employee.id).is_none()).count();
+    let new_departments = directory
+        .departments()
+        .filter(|department| before.department(&department.name).is_none())
+        .count();
+    let memberships = |directory: &Directory| directory.departments().map(|department| department.members.len()).sum::<usize>();
+    let new_memberships = memberships(&directory).saturating_sub(memberships(before));
+
+    let mut lines = vec![
+        if dry_run {
+            format!("Dry run of {}, nothing was changed. Importing it would add:", path)
+        } else {
+            format!("Imported {}:", path)
+        },
+        format!("  {} new employees", new_employees),
+        format!("  {} new departments", new_departments),
+        format!("  {} new memberships", new_memberships),
+        format!("  {} rows read, {} entries were already there", plan.rows, already_there),
+    ];
+    if !errors.is_empty() {
+        let summary = match errors.len() {
+            1 => "1 row had problems and was left out:".to_string(),
+            count => format!("{} rows had problems and were left out:", count),
+        };
+        lines.push(summary);
+        lines.extend(errors.into_iter().map(|(line, error)| format!("  line {}: {}", line, error)));
+    }
+    if !without_manager.is_empty() {
+        let summary = match without_manager.len() {
+            1 => "1 row was imported, but without its manager:".to_string(),
+            count => format!("{} rows were imported, but without their manager:", count),
+        };
+        lines.push(summary);
+        lines.extend(without_manager.into_iter().map(|(line, error)| format!("  line {}: {}", line, error)));
+    }
+    lines.extend(plan.warnings.into_iter().map(|warning| format!("Warning: {}", warning)));
+
+    if dry_run || directory == session.directory {
+        return Ok(lines);
+    }
+    let description = Command::Import { path: path.to_string(), dry_run: false }.to_string();
+    if let Some(storage) = &mut session.storage {
+        storage
+            .log(&description, &applied)
+            .map_err(|error| DirectoryError::Storage(error.to_string()))?;
+    }
+    let before = std::mem::replace(&mut session.directory, directory);
+    session.history.record(description, before, session.directory.clone());
+    Ok(lines)
+}
+
+fn handle_export_command(path: &str, directory: &Directory) -> Result<Vec<String>, DirectoryError> {
+    let (text, rows) = export_roster(directory)?;
+    write_atomically(Path::new(path), text.as_bytes()).map_err(|error| DirectoryError::File {
+        path: path.to_string(),
+        error: error.to_string(),
+    })?;
+    Ok(vec![format!("Exported {} rows to {}", rows, path)])
+}
+
+fn handle_remove_command(name: &str, department: &str, directory: &mut Directory) -> Result<Vec<String>, DirectoryError> {
+    let id = directory.resolve(name)?;
+    directory.remove_member(department, id)?;
+    let department = directory.department(department).expect("was just in it");
+    Ok(vec![format!(
+        "Removed {} from the {} department ({} left in it)",
+        directory.name_of(id),
+        department.name,
+        people(department.members.len())
+    )])
 }
 
-pub fn handle_command(command: String, hash_table: &mut HashMap<String, Vec<String>>) {
-    let command: Vec<&str> = command.split_whitespace().collect();
-    // Handle add command
-    if command.len() == 4 && command[0].to_ascii_lowercase() == "add" {
-        let name = command[1];
-        let department = command[3];
-        handle_add_command(name, department, hash_table);
-    // Handle list command
-    } else if command.len() == 2 && command[0].to_ascii_lowercase() == "list" {
-        let department = command[1];
-        handle_list_department(department, hash_table)
-    // Handle list-all command
-    } else if command.len() == 1 && command[0].to_ascii_lowercase() == "list-all" {
-        handle_list_company(hash_table);
+fn handle_move_command(
+    name: &str,
+    from_department: &str,
+    to_department: &str,
+    directory: &mut Directory,
+) -> Result<Vec<String>, DirectoryError> {
+    let id = directory.resolve(name)?;
+    let mut warnings = Vec::new();
+    if let Some(existing) = directory.department(to_department) {
+        warnings.extend(spelling_warning(to_department, &existing.name, "department"));
     }
+    directory.move_member(id, from_department, to_department)?;
+    let mut lines = vec![format!(
+        "Moved {} from the {} department to the {} department",
+        directory.name_of(id),
+        directory.department(from_department).expect("was just in it").name,
+        directory.department(to_department).expect("just moved to it").name
+    )];
+    lines.extend(warnings);
+    Ok(lines)
+}
+
+fn handle_rename_department(
+    old_name: &str,
+    new_name: &str,
+    directory: &mut Directory,
+) -> Result<Vec<String>, DirectoryError> {
+    let old_name = directory.department(old_name).map_or(old_name.to_string(), |department| department.name.clone());
+    directory.rename_department(&old_name, new_name)?;
+    Ok(vec![format!(
+        "Renamed the {} department to {}",
+        old_name,
+        directory.department(new_name).expect("just renamed").name
+    )])
+}
+
+fn handle_delete_department(department: &str, directory: &mut Directory) -> Result<Vec<String>, DirectoryError> {
+    let children: Vec<String> = match directory.department(department) {
+        Some(department) => directory
+            .subdepartments(Some(department))
+            .into_iter()
+            .map(|child| child.name.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+    let department = directory.delete_department(department)?;
+    let mut lines = vec![format!(
+        "Deleted the {} department ({} members removed with it)",
+        department.name,
+        department.members.len()
+    )];
+    if !children.is_empty() {
+        let destination = match directory.parent(&department) {
+            Some(parent) => format!("inside {}", parent.name),
+            None => "at the top level".to_string(),
+        };
+        lines.push(format!("Moved {} up, they are now {}", children.join(", "), destination));
+    }
+    Ok(lines)
+}
+
+fn handle_create_department(name: &str, parent: Option<&str>, directory: &mut Directory) -> Result<Vec<String>, DirectoryError> {
+    directory.create_department(name, parent)?;
+    let department = directory.department(name).expect("just created");
+    Ok(vec![match directory.parent(department) {
+        Some(parent) => format!("Created the {} department inside {}", department.name, parent.name),
+        None => format!("Created the {} department", department.name),
+    }])
 }
 
-fn handle_add_command(name: &str, department: &str, hash_table: &mut HashMap<String, Vec<String>>) {
-    hash_table.insert(department.to_string(), vec![name.to_string()]);
-    println!("Added {} to the {} department", name, department)
+// Moves a department, and everything inside it, under `parent` or to the
+// top level.
+fn handle_reparent_command(department: &str, parent: Option<&str>, directory: &mut Directory) -> Result<Vec<String>, DirectoryError> {
+    directory.set_parent(department, parent)?;
+    let department = directory.department(department).expect("just moved");
+    Ok(vec![match directory.parent(department) {
+        Some(parent) => format!("Moved the {} department inside {}", department.name, parent.name),
+        None => format!("Moved the {} department to the top level", department.name),
+    }])
 }
 
-fn handle_list_department(department: &str, hash_table: &mut HashMap<String, Vec<String>>) {
-    if let Some(dep_vec)= hash_table.get_mut(department) {
-        dep_vec.sort(); 
-        let mut count = 1;
-        for name in dep_vec {
-            println!("{}. {}", count, name);
-            count += 1;
+// Every department as an indented tree, with how many people are in it and,
+// for departments with others inside them, how many are in the whole branch.
+// The records have one row per department in the same order.
+fn handle_org_chart(store: &impl DirectoryStore) -> Output {
+    let top_level = store.subdepartments(None);
+    let mut lines = Vec::new();
+    let mut rows = Vec::new();
+    for department in &top_level {
+        lines.push(org_chart_label(department, store));
+        org_chart_branch(department, "", store, &mut lines);
+        for below in store.departments_below(department) {
+            rows.push(vec![
+                json!(below.name),
+                json!(store.parent(&below).map(|parent| parent.name)),
+                json!(below.members.len()),
+                json!(store.members_below(&below).len()),
+            ]);
         }
+    }
+    if top_level.is_empty() {
+        lines.push("There are no departments yet.".to_string());
+    }
+    let columns = ["name", "parent", "people", "total"].map(String::from).to_vec();
+    Output::Listing { text: lines, columns, rows }
+}
+
+fn org_chart_branch(department: &Department, indent: &str, store: &impl DirectoryStore, lines: &mut Vec<String>) {
+    let children = store.subdepartments(Some(department));
+    for (index, child) in children.iter().enumerate() {
+        let is_last = index + 1 == children.len();
+        let (branch, next_indent) = if is_last { ("`-- ", "    ") } else { ("|-- ", "|   ") };
+        lines.push(format!("{}{}{}", indent, branch, org_chart_label(child, store)));
+        org_chart_branch(child, &format!("{}{}", indent, next_indent), store, lines);
+    }
+}
+
+fn org_chart_label(department: &Department, store: &impl DirectoryStore) -> String {
+    if store.subdepartments(Some(department)).is_empty() {
+        format!("{} ({})", department.name, people(department.members.len()))
+    } else {
+        format!(
+            "{} ({}, {} in total)",
+            department.name,
+            people(department.members.len()),
+            store.members_below(department).len()
+        )
+    }
+}
+
+fn people(count: usize) -> String {
+    if count == 1 { "1 person".to_string() } else { format!("{} people", count) }
+}
+
+// Head counts only count the people directly in a department, like the
+// first number in the org chart. The largest and smallest are picked from
+// the departments that have anybody in them, the empty ones are listed on
+// their own. Everything comes back as a single record.
+fn handle_stats_command(store: &impl DirectoryStore) -> Output {
+    let departments = store.departments();
+    let columns = ["headcounts", "largest", "smallest", "in_several", "empty", "median", "mode"]
+        .map(String::from)
+        .to_vec();
+    if departments.is_empty() {
+        let text = vec!["There are no departments yet.".to_string()];
+        return Output::Listing { text, columns, rows: Vec::new() };
+    }
+
+    let headcounts: Vec<i32> = departments.iter().map(|department| department.members.len() as i32).collect();
+    let staffed: Vec<&Department> = departments.iter().filter(|department| !department.members.is_empty()).collect();
+    let largest_count = staffed.iter().map(|department| department.members.len()).max();
+    let smallest_count = staffed.iter().map(|department| department.members.len()).min();
+    let with_count = |count: Option<usize>| -> Vec<&str> {
+        staffed
+            .iter()
+            .filter(|department| Some(department.members.len()) == count)
+            .map(|department| department.name.as_str())
+            .collect()
+    };
+    let largest = with_count(largest_count);
+    let smallest = with_count(smallest_count);
+    let empty: Vec<&str> = departments
+        .iter()
+        .filter(|department| department.members.is_empty())
+        .map(|department| department.name.as_str())
+        .collect();
+    let in_several: Vec<(Employee, Vec<String>)> = store
+        .employees()
+        .into_iter()
+        .map(|employee| {
+            let departments = store.departments_of(employee.id);
+            (employee, departments)
+        })
+        .filter(|(_, departments)| departments.len() > 1)
+        .collect();
+    // Without any departments there is neither.
+    let median = find_median(&headcounts).ok();
+    let mode = _find_mode(&headcounts).ok();
+
+    let mut lines = vec!["Head count by department:".to_string()];
+    for department in &departments {
+        lines.push(format!("  {}: {}", department.name, people(department.members.len())));
+    }
+    if let (Some(largest_count), Some(smallest_count)) = (largest_count, smallest_count) {
+        lines.push(format!("Largest: {} ({})", largest.join(", "), people(largest_count)));
+        lines.push(format!("Smallest: {} ({})", smallest.join(", "), people(smallest_count)));
+    }
+    if in_several.is_empty() {
+        lines.push("Nobody is in more than one department.".to_string());
     } else {
-        println!("Nobody was found in the {} department", department);
+        lines.push("In more than one department:".to_string());
+        for (employee, departments) in &in_several {
+            lines.push(format!("  {} (#{}): {}", employee.name, employee.id, departments.join(", ")));
+        }
     }
+    if !empty.is_empty() {
+        lines.push(format!("Departments with nobody in them: {}", empty.join(", ")));
+    }
+    if let (Some(median), Some(mode)) = (median, mode) {
+        lines.push(format!("Median head count: {}", median));
+        lines.push(format!("Most common head count: {}", mode));
+    }
+
+    let headcounts: serde_json::Map<String, Value> = departments
+        .iter()
+        .map(|department| (department.name.clone(), json!(department.members.len())))
+        .collect();
+    let in_several: Vec<Value> = in_several
+        .iter()
+        .map(|(employee, departments)| json!({ "id": employee.id, "name": employee.name, "departments": departments }))
+        .collect();
+    let median = match median {
+        Some(MedianResult::Int(int_num)) => json!(int_num),
+        Some(MedianResult::Float(floating_num)) => json!(floating_num),
+        None => Value::Null,
+    };
+    let row = vec![
+        Value::Object(headcounts),
+        json!(largest),
+        json!(smallest),
+        json!(in_several),
+        json!(empty),
+        median,
+        json!(mode),
+    ];
+    Output::Listing { text: lines, columns, rows: vec![row] }
 }
 
-fn handle_list_company(hash_table: &mut HashMap<String, Vec<String>>) {
-    if hash_table.is_empty() {
-        println!("Nobody is currently employed to any department.");
-        return
+// `value` of None clears the field.
+fn handle_set_command(
+    employee: &str,
+    field: Field,
+    value: Option<&str>,
+    directory: &mut Directory,
+) -> Result<Vec<String>, DirectoryError> {
+    let id = directory.resolve(employee)?;
+    let previous = directory.set_attribute(id, field, value)?;
+    let shown = |value: Option<String>| match (field, value) {
+        (Field::Manager, Some(manager)) => manager_label(directory, &manager),
+        (_, Some(value)) => value,
+        (_, None) => "nothing".to_string(),
+    };
+    let mut lines = vec![format!(
+        "Changed the {} of #{} from {} to {}",
+        field.as_str(),
+        id,
+        shown(previous),
+        shown(directory.attribute(id, field))
+    )];
+    if field == Field::Name {
+        let namesakes: Vec<String> = directory
+            .same_name(&directory.name_of(id), Some(id))
+            .into_iter()
+            .map(|other| format!("{} (#{})", directory.name_of(other), other))
+            .collect();
+        if !namesakes.is_empty() {
+            lines.push(format!(
+                "Warning: that name is already used by {}, so use ids to tell them apart",
+                namesakes.join(", ")
+            ));
+        }
     }
+    Ok(lines)
+}
 
-    let mut all_people: Vec<String> = hash_table.values().flatten().cloned().collect();
-    all_people.sort();
-    for (index, person) in all_people.iter().enumerate() {
-        println!("{}. {}", index + 1, person);
+// Turns a stored `#3` manager reference into `Sally (#3)`.
+fn manager_label(directory: &Directory, manager: &str) -> String {
+    match directory.resolve(manager) {
+        Ok(id) => employee_label(directory, id),
+        Err(_) => manager.to_string(),
     }
-} 
\ No newline at end of file
+}
+
+fn employee_label(directory: &Directory, id: EmployeeId) -> String {
+    format!("{} (#{})", directory.name_of(id), id)
+}
+
+// Makes `employee` report to `manager`, as long as that doesn't make a loop.
+fn handle_assign_command(employee: &str, manager: &str, directory: &mut Directory) -> Result<Vec<String>, DirectoryError> {
+    let id = directory.resolve(employee)?;
+    let previous = directory.set_attribute(id, Field::Manager, Some(manager))?;
+    let manager = directory.attribute(id, Field::Manager).expect("just set");
+    let mut line = format!("{} now reports to {}", employee_label(directory, id), manager_label(directory, &manager));
+    if let Some(previous) = previous.filter(|previous| *previous != manager) {
+        line.push_str(&format!(" instead of {}", manager_label(directory, &previous)));
+    }
+    Ok(vec![line])
+}
+
+// Everybody below `employee`, as a tree of who reports to whom.
+fn handle_reports_command(employee: &str, directory: &Directory) -> Result<Vec<String>, DirectoryError> {
+    let id = directory.resolve(employee)?;
+    let mut tree = Vec::new();
+    reports_tree(id, 1, directory, &mut tree);
+    if tree.is_empty() {
+        return Ok(vec![format!("Nobody reports to {}", employee_label(directory, id))]);
+    }
+    let direct = directory.direct_reports(id).len();
+    let mut lines = vec![format!(
+        "{} has {} direct and {} total reports:",
+        employee_label(directory, id),
+        direct,
+        tree.len()
+    )];
+    lines.extend(tree);
+    Ok(lines)
+}
+
+fn reports_tree(id: EmployeeId, depth: usize, directory: &Directory, lines: &mut Vec<String>) {
+    for report in directory.direct_reports(id) {
+        lines.push(format!("{}{}", "  ".repeat(depth), employee_label(directory, report)));
+        reports_tree(report, depth + 1, directory, lines);
+    }
+}
+
+// `employee`, their manager, and so on up to somebody who reports to nobody.
+fn handle_chain_command(employee: &str, directory: &Directory) -> Result<Vec<String>, DirectoryError> {
+    let id = directory.resolve(employee)?;
+    let chain = directory.management_chain(id);
+    if chain.is_empty() {
+        return Ok(vec![format!("{} doesn't report to anybody", employee_label(directory, id))]);
+    }
+    let mut labels = vec![employee_label(directory, id)];
+    labels.extend(chain.into_iter().map(|manager| employee_label(directory, manager)));
+    Ok(vec![labels.join(" -> ")])
+}
+
+fn handle_show_command(employee: &str, directory: &Directory) -> Result<Vec<String>, DirectoryError> {
+    let id = directory.resolve(employee)?;
+    let mut lines = vec![format!("id: #{}", id)];
+    for field in Field::ALL {
+        let value = match (field, directory.attribute(id, field)) {
+            (Field::Manager, Some(manager)) => manager_label(directory, &manager),
+            (_, Some(value)) => value,
+            (_, None) => "-".to_string(),
+        };
+        lines.push(format!("{}: {}", field.as_str(), value));
+    }
+    let departments = directory.departments_of(id);
+    if departments.is_empty() {
+        lines.push("departments: -".to_string());
+    } else {
+        lines.push(format!("departments: {}", departments.join(", ")));
+    }
+    Ok(lines)
+}
+
+fn handle_find_command(query: &str, directory: &Directory) -> Output {
+    let hits = find(directory, query);
+    let text = if hits.is_empty() {
+        vec![format!("Nobody matches {}", query)]
+    } else {
+        hits.iter()
+            .enumerate()
+            .map(|(index, (kind, employee))| {
+                let departments = directory.departments_of(employee.id);
+                let departments = if departments.is_empty() {
+                    "no department".to_string()
+                } else {
+                    departments.join(", ")
+                };
+                format!("{}. {} (#{}) in {} - {}", index + 1, employee.name, employee.id, departments, kind.as_str())
+            })
+            .collect()
+    };
+
+    // The records also say how each name matched.
+    let employees: Vec<Employee> = hits.iter().map(|(_, employee)| (*employee).clone()).collect();
+    let (mut columns, mut rows) = employee_records(&employees, &[], directory);
+    columns.push("match".to_string());
+    for (row, (kind, _)) in rows.iter_mut().zip(&hits) {
+        row.push(json!(kind.as_str()));
+    }
+    Output::Listing { text, columns, rows }
+}
+
+// With `recursive` everybody in the departments inside `department` is
+// listed too, with the departments of that branch they are in.
+fn handle_list_department(department: &str, recursive: bool, columns: &[Column], store: &impl DirectoryStore) -> Output {
+    let (branch, members) = match store.department(department) {
+        Some(department) if recursive => (store.departments_below(&department), store.members_below(&department)),
+        Some(department) => (vec![department.clone()], department.members),
+        None => (Vec::new(), Vec::new()),
+    };
+    if members.is_empty() {
+        let text = vec![format!("Nobody was found in the {} department", department)];
+        return employee_listing(text, &[], columns, store);
+    }
+
+    let mut members: Vec<Employee> = members.iter().filter_map(|id| store.employee(*id)).collect();
+    members.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    let text = if columns.is_empty() && recursive {
+        members
+            .iter()
+            .enumerate()
+            .map(|(index, employee)| {
+                let departments: Vec<&str> = branch
+                    .iter()
+                    .filter(|department| department.members.contains(&employee.id))
+                    .map(|department| department.name.as_str())
+                    .collect();
+                format!("{}. {} ({})", index + 1, employee.name, departments.join(", "))
+            })
+            .collect()
+    } else if columns.is_empty() {
+        members
+            .iter()
+            .enumerate()
+            .map(|(index, employee)| format!("{}. {}", index + 1, employee.name))
+            .collect()
+    } else {
+        format_table(&members, columns, store)
+    };
+    employee_listing(text, &members, columns, store)
+}
+
+fn handle_list_company(columns: &[Column], store: &impl DirectoryStore) -> Output {
+    let mut all_people = store.employees();
+    if all_people.is_empty() {
+        let text = vec!["Nobody is currently employed to any department.".to_string()];
+        return employee_listing(text, &[], columns, store);
+    }
+
+    all_people.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    let text = if columns.is_empty() {
+        // People in several departments are listed once, with all of their departments.
+        all_people
+            .iter()
+            .enumerate()
+            .map(|(index, employee)| {
+                let departments = store.departments_of(employee.id);
+                if departments.is_empty() {
+                    format!("{}. {}", index + 1, employee.name)
+                } else {
+                    format!("{}. {} ({})", index + 1, employee.name, departments.join(", "))
+                }
+            })
+            .collect()
+    } else {
+        format_table(&all_people, columns, store)
+    };
+    employee_listing(text, &all_people, columns, store)
+}
+
+fn employee_listing(text: Vec<String>, employees: &[Employee], columns: &[Column], store: &impl DirectoryStore) -> Output {
+    let (columns, rows) = employee_records(employees, columns, store);
+    Output::Listing { text, columns, rows }
+}
+
+// `employees` as records for `output`, with the columns asked for with
+// `with`, or all of them when there weren't any.
+fn employee_records(employees: &[Employee], columns: &[Column], store: &impl DirectoryStore) -> (Vec<String>, Vec<Vec<Value>>) {
+    let columns = if columns.is_empty() { &Column::ALL[..] } else { columns };
+    let rows = employees
+        .iter()
+        .map(|employee| columns.iter().map(|column| column_record(employee, *column, store)).collect())
+        .collect();
+    (columns.iter().map(|column| column.as_str().to_string()).collect(), rows)
+}
+
+// Like `column_value`, but typed for `output`: ids are numbers, departments
+// a list and missing values null.
+fn column_record(employee: &Employee, column: Column, store: &impl DirectoryStore) -> Value {
+    match column {
+        Column::Id => json!(employee.id),
+        Column::Name => json!(employee.name),
+        Column::Title => json!(employee.title),
+        Column::Email => json!(employee.email),
+        Column::StartDate => json!(employee.start_date),
+        Column::Manager => json!(employee.manager),
+        Column::Departments => json!(store.departments_of(employee.id)),
+    }
+}
+
+fn column_value(employee: &Employee, column: Column, store: &impl DirectoryStore) -> String {
+    let value = match column {
+        Column::Id => Some(format!("#{}", employee.id)),
+        Column::Name => Some(employee.name.clone()),
+        Column::Title => employee.title.clone(),
+        Column::Email => employee.email.clone(),
+        Column::StartDate => employee.start_date.clone(),
+        Column::Manager => employee.manager.map(|manager| store.name_of(manager)),
+        Column::Departments => Some(store.departments_of(employee.id).join(", ")).filter(|joined| !joined.is_empty()),
+    };
+    value.unwrap_or_else(|| "-".to_string())
+}
+
+// One row per employee with the columns lined up under a header.
+fn format_table(employees: &[Employee], columns: &[Column], store: &impl DirectoryStore) -> Vec<String> {
+    let rows: Vec<Vec<String>> = employees
+        .iter()
+        .map(|employee| columns.iter().map(|column| column_value(employee, *column, store)).collect())
+        .collect();
+    let mut widths: Vec<usize> = columns.iter().map(|column| column.as_str().chars().count()).collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let header: Vec<String> = columns.iter().map(|column| column.as_str().to_string()).collect();
+    std::iter::once(header)
+        .chain(rows)
+        .map(|row| {
+            let row: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{:<width$}", value, width = width))
+                .collect();
+            row.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
@@ -0,0 +1,171 @@
+// A small CSV reader and writer, enough for the rosters HR hands out.
+//
+// Fields are separated by commas and records by newlines (`\n` or `\r\n`).
+// A field wrapped in double quotes can contain commas, newlines and quotes,
+// with a quote written twice (`""`) meaning one literal quote. Blank lines
+// are skipped.
+//
+// A broken record doesn't stop the rest of the file from being read: it is
+// returned as an error for its line and reading carries on with the next
+// line. That includes a quote that is never closed, which would otherwise
+// swallow everything after it.
+
+// One record, with the line it starts on (counting from 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRecord {
+    pub line: usize,
+    pub fields: Result<Vec<String>, String>,
+}
+
+pub fn parse_csv(text: &str) -> Vec<CsvRecord> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut records = Vec::new();
+    let mut position = 0;
+    let mut line = 1;
+
+    while position < chars.len() {
+        let start = position;
+        let start_line = line;
+        match parse_record(&chars, &mut position, &mut line) {
+            Ok(fields) => {
+                if !(fields.len() == 1 && fields[0].trim().is_empty()) {
+                    records.push(CsvRecord { line: start_line, fields: Ok(fields) });
+                }
+            }
+            Err(error) => {
+                records.push(CsvRecord { line: start_line, fields: Err(error) });
+                // Start again on the line after the one the broken record started on.
+                position = chars[start..]
+                    .iter()
+                    .position(|c| *c == '\n')
+                    .map_or(chars.len(), |offset| start + offset + 1);
+                line = start_line + 1;
+            }
+        }
+    }
+    records
+}
+
+// Reads one record starting at `position` and leaves `position` at the
+// start of the next one.
+fn parse_record(chars: &[char], position: &mut usize, line: &mut usize) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+
+    loop {
+        let Some(&c) = chars.get(*position) else {
+            fields.push(field);
+            return Ok(fields);
+        };
+        *position += 1;
+        match c {
+            '\n' => {
+                *line += 1;
+                fields.push(field);
+                return Ok(fields);
+            }
+            '\r' if chars.get(*position) == Some(&'\n') => {}
+            ',' => fields.push(std::mem::take(&mut field)),
+            '"' if field.is_empty() => {
+                // A quoted field runs until a quote that isn't doubled.
+                loop {
+                    let Some(&c) = chars.get(*position) else {
+                        return Err("a quoted field is never closed".to_string());
+                    };
+                    *position += 1;
+                    match c {
+                        '"' if chars.get(*position) == Some(&'"') => {
+                            *position += 1;
+                            field.push('"');
+                        }
+                        '"' => break,
+                        '\n' => {
+                            *line += 1;
+                            field.push('\n');
+                        }
+                        c => field.push(c),
+                    }
+                }
+                match chars.get(*position) {
+                    None | Some(',') | Some('\n') | Some('\r') => {}
+                    Some(c) => return Err(format!("unexpected `{}` after a closing quote", c)),
+                }
+            }
+            '"' => return Err("a quote appears in the middle of an unquoted field".to_string()),
+            c => field.push(c),
+        }
+    }
+}
+
+// Quotes a field if it would otherwise be read back differently.
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.starts_with(' ') || value.ends_with(' ') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn csv_line(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+    fields.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(text: &str) -> Vec<Result<Vec<String>, String>> {
+        parse_csv(text).into_iter().map(|record| record.fields).collect()
+    }
+
+    fn row(fields: &[&str]) -> Result<Vec<String>, String> {
+        Ok(fields.iter().map(|field| field.to_string()).collect())
+    }
+
+    #[test]
+    fn plain_fields_and_both_line_endings() {
+        assert_eq!(fields("a,b\r\nc,d\n"), [row(&["a", "b"]), row(&["c", "d"])]);
+        assert_eq!(fields("a,,\n"), [row(&["a", "", ""])]);
+    }
+
+    #[test]
+    fn blank_lines_are_skipped_but_counted() {
+        let records = parse_csv("a\n\n  \nb\n");
+        assert_eq!(records.iter().map(|record| record.line).collect::<Vec<_>>(), [1, 4]);
+    }
+
+    #[test]
+    fn quoted_fields_keep_commas_quotes_and_newlines() {
+        let records = parse_csv("\"Doe, Jane\",\"say \"\"hi\"\"\"\n\"two\nlines\",x\nlast\n");
+        assert_eq!(records[0].fields, row(&["Doe, Jane", "say \"hi\""]));
+        assert_eq!(records[1].fields, row(&["two\nlines", "x"]));
+        // The line after a multi-line field is still counted right.
+        assert_eq!((records[1].line, records[2].line), (2, 4));
+    }
+
+    #[test]
+    fn a_broken_record_only_loses_its_own_line() {
+        let records = parse_csv("\"never closed,a\nb,c\n\"x\"y,z\nmid\"dle\nd\n");
+        assert!(records[0].fields.is_err());
+        assert_eq!((records[1].line, records[1].fields.clone()), (2, row(&["b", "c"])));
+        assert!(records[2].fields.as_ref().unwrap_err().contains("after a closing quote"));
+        assert!(records[3].fields.as_ref().unwrap_err().contains("middle"));
+        assert_eq!((records[4].line, records[4].fields.clone()), (5, row(&["d"])));
+    }
+
+    #[test]
+    fn written_fields_read_back_the_same() {
+        let written = vec![
+            "plain".to_string(),
+            "Doe, Jane".to_string(),
+            "say \"hi\"".to_string(),
+            "two\nlines".to_string(),
+            " padded ".to_string(),
+            String::new(),
+        ];
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(" padded "), "\" padded \"");
+        assert_eq!(fields(&csv_line(&written)), [Ok(written)]);
+    }
+}
@@ -0,0 +1,730 @@
+// The company directory: every employee with their attributes, and which
+// departments they belong to.
+//
+// Employees get an id when they are first added. Ids are never reused, even
+// after the employee is gone, so anything that remembers an id keeps pointing
+// at the same person. Commands can refer to somebody either by id (`#3` or
+// just `3`) or by name, as long as only one person has that name.
+//
+// Names of people and departments are compared through `names::name_key`,
+// so `engineering` finds the Engineering department and `bob` finds Bob.
+// The spelling a department or person was first given is kept for output.
+//
+// Departments can sit inside another department, like Engineering > Platform
+// > Storage. A department only knows its parent, the tree is worked out from
+// those. Members of a sub-department aren't members of its parents, asking
+// for everybody below a department collects them.
+//
+// People work the same way through their `manager`: everybody reports to at
+// most one person, and nobody can end up managing themselves through a loop
+// of managers.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::names::{display_name, name_key};
+
+pub type EmployeeId = u32;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Employee {
+    pub id: EmployeeId,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    // Always YYYY-MM-DD, checked by `set`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manager: Option<EmployeeId>,
+}
+
+impl Employee {
+    fn new(id: EmployeeId, name: &str) -> Self {
+        Employee {
+            id,
+            name: name.to_string(),
+            title: None,
+            email: None,
+            start_date: None,
+            manager: None,
+        }
+    }
+}
+
+// The attributes `set` and `unset` can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Title,
+    Email,
+    StartDate,
+    Manager,
+}
+
+impl Field {
+    pub const ALL: [Field; 5] = [Field::Name, Field::Title, Field::Email, Field::StartDate, Field::Manager];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Field::Name => "name",
+            Field::Title => "title",
+            Field::Email => "email",
+            Field::StartDate => "start-date",
+            Field::Manager => "manager",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Field> {
+        Field::ALL.into_iter().find(|field| field.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryError {
+    NoSuchDepartment(String),
+    NoSuchEmployee(String),
+    // More than one person has this name, so it has to be given as an id.
+    AmbiguousName { name: String, ids: Vec<EmployeeId> },
+    NotInDepartment { name: String, department: String },
+    AlreadyInDepartment { name: String, department: String },
+    AlreadyInDirectory(String),
+    DepartmentExists(String),
+    // Making `manager` the manager of `employee` would make a loop, because
+    // `manager` already reports to `employee`, directly or not.
+    ManagementCycle { employee: String, manager: String },
+    // Putting `department` under `parent` would make it its own ancestor.
+    DepartmentCycle { department: String, parent: String },
+    InvalidValue { field: Field, value: String, reason: &'static str },
+    NothingToUndo,
+    NothingToRedo,
+    // Saving the change failed, so it was not made.
+    Storage(String),
+    // Only commands that work on the directory alone can be replayed as steps.
+    NotAStep(String),
+    // A file the command was given couldn't be read or written.
+    File { path: String, error: String },
+    // A roster only has names, so it can't tell these people apart.
+    SharedName { name: String, ids: Vec<EmployeeId> },
+    // Ctrl-C stopped the command before it changed anything.
+    Interrupted,
+}
+
+impl DirectoryError {
+    // A short name for the kind of error that programs can match on, see
+    // `output`. These never change once they are in use.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DirectoryError::NoSuchDepartment(_) => "no_such_department",
+            DirectoryError::NoSuchEmployee(_) => "no_such_employee",
+            DirectoryError::AmbiguousName { .. } => "ambiguous_name",
+            DirectoryError::NotInDepartment { .. } => "not_in_department",
+            DirectoryError::AlreadyInDepartment { .. } => "already_in_department",
+            DirectoryError::AlreadyInDirectory(_) => "already_in_directory",
+            DirectoryError::DepartmentExists(_) => "department_exists",
+            DirectoryError::ManagementCycle { .. } => "management_cycle",
+            DirectoryError::DepartmentCycle { .. } => "department_cycle",
+            DirectoryError::InvalidValue { .. } => "invalid_value",
+            DirectoryError::NothingToUndo => "nothing_to_undo",
+            DirectoryError::NothingToRedo => "nothing_to_redo",
+            DirectoryError::Storage(_) => "storage",
+            DirectoryError::NotAStep(_) => "not_a_step",
+            DirectoryError::File { .. } => "file",
+            DirectoryError::SharedName { .. } => "shared_name",
+            DirectoryError::Interrupted => "interrupted",
+        }
+    }
+}
+
+impl fmt::Display for DirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DirectoryError::NoSuchDepartment(department) => write!(f, "there is no {} department", department),
+            DirectoryError::NoSuchEmployee(reference) => write!(f, "nobody matching {} is in the directory", reference),
+            DirectoryError::AmbiguousName { name, ids } => {
+                let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+                write!(f, "several people are called {} ({}), use an id instead", name, ids.join(", "))
+            }
+            DirectoryError::NotInDepartment { name, department } => {
+                write!(f, "{} is not in the {} department", name, department)
+            }
+            DirectoryError::AlreadyInDepartment { name, department } => {
+                write!(f, "{} is already in the {} department", name, department)
+            }
+            DirectoryError::AlreadyInDirectory(name) => write!(f, "{} is already in the directory", name),
+            DirectoryError::DepartmentExists(department) => write!(f, "a department named {} already exists", department),
+            DirectoryError::ManagementCycle { employee, manager } => {
+                write!(f, "{} can't report to {}, who already reports to {}", employee, manager, employee)
+            }
+            DirectoryError::DepartmentCycle { department, parent } if department == parent => {
+                write!(f, "{} can't be put under itself", department)
+            }
+            DirectoryError::DepartmentCycle { department, parent } => {
+                write!(f, "{} can't be put under {}, which is already inside it", department, parent)
+            }
+            DirectoryError::InvalidValue { field, value, reason } if value.is_empty() => {
+                write!(f, "the {} can't be cleared: {}", field.as_str(), reason)
+            }
+            DirectoryError::InvalidValue { field, value, reason } => {
+                write!(f, "{} is not a valid {}: {}", value, field.as_str(), reason)
+            }
+            DirectoryError::NothingToUndo => write!(f, "there is nothing to undo"),
+            DirectoryError::NothingToRedo => write!(f, "there is nothing to redo"),
+            DirectoryError::Storage(error) => write!(f, "could not save the change: {}", error),
+            DirectoryError::NotAStep(command) => write!(f, "`{}` can't be run as part of another command", command),
+            DirectoryError::File { path, error } => write!(f, "{}: {}", path, error),
+            DirectoryError::SharedName { name, ids } => {
+                let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+                write!(f, "several people are called {} ({}), rename them so a roster can tell them apart", name, ids.join(", "))
+            }
+            DirectoryError::Interrupted => write!(f, "interrupted, nothing was changed"),
+        }
+    }
+}
+
+impl std::error::Error for DirectoryError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Department {
+    // The spelling the department was created (or last renamed) with.
+    pub name: String,
+    // In the order they joined.
+    pub members: Vec<EmployeeId>,
+    // The `name_key` of the department this one sits in, None at the top.
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directory {
+    next_id: EmployeeId,
+    employees: BTreeMap<EmployeeId, Employee>,
+    // Keyed by the `name_key` of the department's name.
+    departments: BTreeMap<String, Department>,
+}
+
+impl Default for Directory {
+    fn default() -> Self {
+        Directory::new()
+    }
+}
+
+impl Directory {
+    pub fn new() -> Self {
+        Directory {
+            next_id: 1,
+            employees: BTreeMap::new(),
+            departments: BTreeMap::new(),
+        }
+    }
+
+    // Builds a directory out of the old name-only layout, where the same name
+    // in two departments meant the same person. Ids are handed out in name
+    // order so converting the same data twice gives the same ids. Also
+    // returns warnings about departments that had to be merged, like
+    // `from_parts`.
+    pub fn from_department_names(hash_table: &HashMap<String, Vec<String>>) -> (Self, Vec<String>) {
+        let mut directory = Directory::new();
+        let mut names: Vec<&String> = hash_table.values().flatten().collect();
+        names.sort();
+        names.dedup();
+        let mut ids: HashMap<&String, EmployeeId> = HashMap::new();
+        for name in names {
+            ids.insert(name, directory.create_employee(name));
+        }
+        let mut departments: Vec<(&String, &Vec<String>)> = hash_table.iter().collect();
+        departments.sort();
+        let mut warnings = Vec::new();
+        for (department, members) in departments {
+            let members = members.iter().map(|name| ids[name]).collect();
+            directory.merge_department(department, members, &mut warnings);
+        }
+        (directory, warnings)
+    }
+
+    // Puts a directory back together from what `persistence` saved. `next_id`
+    // is bumped past the highest id in use in case the file was hand edited.
+    // A file written before names were compared without case can have two
+    // spellings of one department, those are merged and a warning for each
+    // merge is returned with the directory. `parents` maps a department's
+    // name to the name of the department it sits in, a parent that is
+    // missing or would make a loop is dropped with a warning.
+    pub fn from_parts(
+        next_id: EmployeeId,
+        employees: Vec<Employee>,
+        departments: BTreeMap<String, Vec<EmployeeId>>,
+        parents: BTreeMap<String, String>,
+    ) -> (Self, Vec<String>) {
+        let employees: BTreeMap<EmployeeId, Employee> =
+            employees.into_iter().map(|employee| (employee.id, employee)).collect();
+        let highest_id = employees.keys().max().copied().unwrap_or(0);
+        let mut directory = Directory {
+            next_id: next_id.max(highest_id + 1),
+            employees,
+            departments: BTreeMap::new(),
+        };
+        let mut warnings = Vec::new();
+        for (department, members) in departments {
+            directory.merge_department(&department, members, &mut warnings);
+        }
+        for (department, parent) in parents {
+            if let Err(error) = directory.set_parent(&department, Some(&parent)) {
+                warnings.push(format!("{} was put at the top level because {}", department, error));
+            }
+        }
+        // A hand edited file could have a loop of managers, which is broken
+        // up where it is found.
+        let ids: Vec<EmployeeId> = directory.employees.keys().copied().collect();
+        for id in ids {
+            if directory.management_chain(id).contains(&id) {
+                warnings.push(format!(
+                    "{} (#{}) no longer has a manager, because they were managing themselves through a loop",
+                    directory.name_of(id),
+                    id
+                ));
+                directory.employees.get_mut(&id).expect("listed above").manager = None;
+            }
+        }
+        (directory, warnings)
+    }
+
+    // Adds a loaded department, or its members to the department that
+    // already has the same key.
+    fn merge_department(&mut self, name: &str, members: Vec<EmployeeId>, warnings: &mut Vec<String>) {
+        let department = self.departments.entry(name_key(name)).or_insert_with(|| Department {
+            name: display_name(name),
+            members: Vec::new(),
+            parent: None,
+        });
+        if department.name != display_name(name) {
+            warnings.push(format!(
+                "the departments {} and {} only differ in case or spacing, so they were merged into {}",
+                department.name, name, department.name
+            ));
+        }
+        for id in members {
+            if !department.members.contains(&id) {
+                department.members.push(id);
+            }
+        }
+    }
+
+    pub fn next_id(&self) -> EmployeeId {
+        self.next_id
+    }
+
+    // Replaces the contents with an earlier copy of the directory, for undo.
+    // `next_id` doesn't go backwards, so an id handed out to somebody who was
+    // then undone isn't given to anybody else.
+    pub fn restore(&mut self, earlier: Directory) {
+        let next_id = self.next_id.max(earlier.next_id);
+        *self = earlier;
+        self.next_id = next_id;
+    }
+
+    pub fn employee(&self, id: EmployeeId) -> Option<&Employee> {
+        self.employees.get(&id)
+    }
+
+    pub fn employees(&self) -> impl Iterator<Item = &Employee> {
+        self.employees.values()
+    }
+
+    pub fn department(&self, department: &str) -> Option<&Department> {
+        self.departments.get(&name_key(department))
+    }
+
+    // Sorted by name, ignoring case.
+    pub fn departments(&self) -> impl Iterator<Item = &Department> {
+        self.departments.values()
+    }
+
+    pub fn parent(&self, department: &Department) -> Option<&Department> {
+        department.parent.as_ref().and_then(|parent| self.departments.get(parent))
+    }
+
+    // The names of every department `id` belongs to, sorted like `departments`.
+    pub fn departments_of(&self, id: EmployeeId) -> Vec<&str> {
+        self.departments
+            .values()
+            .filter(|department| department.members.contains(&id))
+            .map(|department| department.name.as_str())
+            .collect()
+    }
+
+    // Everybody other than `except` whose name only differs from `name` in
+    // case or spacing.
+    pub fn same_name(&self, name: &str, except: Option<EmployeeId>) -> Vec<EmployeeId> {
+        let key = name_key(name);
+        self.employees
+            .values()
+            .filter(|employee| Some(employee.id) != except && name_key(&employee.name) == key)
+            .map(|employee| employee.id)
+            .collect()
+    }
+
+    // Everybody whose manager is `id`, sorted by name.
+    pub fn direct_reports(&self, id: EmployeeId) -> Vec<EmployeeId> {
+        let mut reports: Vec<&Employee> = self.employees.values().filter(|employee| employee.manager == Some(id)).collect();
+        reports.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        reports.into_iter().map(|employee| employee.id).collect()
+    }
+
+    // The manager of `id`, their manager and so on up to somebody without
+    // one. Stops early if a manager comes round a second time.
+    pub fn management_chain(&self, id: EmployeeId) -> Vec<EmployeeId> {
+        let mut chain = Vec::new();
+        let mut current = self.employees.get(&id).and_then(|employee| employee.manager);
+        while let Some(manager) = current {
+            if chain.contains(&manager) {
+                break;
+            }
+            chain.push(manager);
+            current = self.employees.get(&manager).and_then(|employee| employee.manager);
+        }
+        chain
+    }
+
+    // The name of `id`, or the id itself if they are not in the directory.
+    pub fn name_of(&self, id: EmployeeId) -> String {
+        match self.employees.get(&id) {
+            Some(employee) => employee.name.clone(),
+            None => format!("#{}", id),
+        }
+    }
+
+    // Finds the employee `reference` points at: `#3` or `3` is an id,
+    // anything else has to be the name of exactly one person.
+    pub fn resolve(&self, reference: &str) -> Result<EmployeeId, DirectoryError> {
+        if is_id_reference(reference) {
+            let digits = reference.strip_prefix('#').unwrap_or(reference);
+            return match digits.parse() {
+                Ok(id) if self.employees.contains_key(&id) => Ok(id),
+                _ => Err(DirectoryError::NoSuchEmployee(format!("#{}", digits))),
+            };
+        }
+
+        let ids = self.same_name(reference, None);
+        match ids.as_slice() {
+            [] => Err(DirectoryError::NoSuchEmployee(reference.to_string())),
+            [id] => Ok(*id),
+            _ => Err(DirectoryError::AmbiguousName { name: reference.to_string(), ids }),
+        }
+    }
+
+    // Like `resolve`, but a name nobody has yet becomes a new employee.
+    // Returns the id and whether the employee was just created. An id that
+    // nobody has is an error, `add 5 to Sales` shouldn't hire someone
+    // called 5.
+    pub fn resolve_or_create(&mut self, reference: &str) -> Result<(EmployeeId, bool), DirectoryError> {
+        match self.resolve(reference) {
+            Ok(id) => Ok((id, false)),
+            Err(DirectoryError::NoSuchEmployee(_)) if !is_id_reference(reference) => {
+                Ok((self.create_employee(reference), true))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn create_employee(&mut self, name: &str) -> EmployeeId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.employees.insert(id, Employee::new(id, &display_name(name)));
+        id
+    }
+
+    // Adds `id` to `department`, creating the department if it doesn't exist.
+    // Returns true when the department was created.
+    pub fn add_member(&mut self, department: &str, id: EmployeeId) -> Result<bool, DirectoryError> {
+        let key = name_key(department);
+        let is_new_department = !self.departments.contains_key(&key);
+        let name = self.name_of(id);
+        let department = self.departments.entry(key).or_insert_with(|| Department {
+            name: display_name(department),
+            members: Vec::new(),
+            parent: None,
+        });
+        if department.members.contains(&id) {
+            return Err(DirectoryError::AlreadyInDepartment { name, department: department.name.clone() });
+        }
+        department.members.push(id);
+        Ok(is_new_department)
+    }
+
+    pub fn remove_member(&mut self, department: &str, id: EmployeeId) -> Result<(), DirectoryError> {
+        let name = self.name_of(id);
+        let Some(department) = self.departments.get_mut(&name_key(department)) else {
+            return Err(DirectoryError::NoSuchDepartment(department.to_string()));
+        };
+        let Some(position) = department.members.iter().position(|member| *member == id) else {
+            return Err(DirectoryError::NotInDepartment { name, department: department.name.clone() });
+        };
+        department.members.remove(position);
+        Ok(())
+    }
+
+    // Moves `id` between departments, creating `to_department` if needed.
+    // Nothing changes unless the whole move can be done.
+    pub fn move_member(&mut self, id: EmployeeId, from_department: &str, to_department: &str) -> Result<(), DirectoryError> {
+        let name = self.name_of(id);
+        let Some(from) = self.department(from_department) else {
+            return Err(DirectoryError::NoSuchDepartment(from_department.to_string()));
+        };
+        if !from.members.contains(&id) {
+            return Err(DirectoryError::NotInDepartment { name, department: from.name.clone() });
+        }
+        if let Some(to) = self.department(to_department)
+            && to.members.contains(&id)
+        {
+            return Err(DirectoryError::AlreadyInDepartment { name, department: to.name.clone() });
+        }
+
+        self.remove_member(from_department, id)?;
+        self.add_member(to_department, id)?;
+        Ok(())
+    }
+
+    // Renaming to another spelling of the same name, like `sales` to
+    // `Sales`, only changes how the department is shown.
+    pub fn rename_department(&mut self, old_name: &str, new_name: &str) -> Result<(), DirectoryError> {
+        let (old_key, new_key) = (name_key(old_name), name_key(new_name));
+        if !self.departments.contains_key(&old_key) {
+            return Err(DirectoryError::NoSuchDepartment(old_name.to_string()));
+        }
+        if new_key != old_key
+            && let Some(existing) = self.departments.get(&new_key)
+        {
+            return Err(DirectoryError::DepartmentExists(existing.name.clone()));
+        }
+        let mut department = self.departments.remove(&old_key).expect("checked above");
+        department.name = display_name(new_name);
+        self.departments.insert(new_key.clone(), department);
+        for child in self.departments.values_mut() {
+            if child.parent.as_ref() == Some(&old_key) {
+                child.parent = Some(new_key.clone());
+            }
+        }
+        Ok(())
+    }
+
+    // Deletes a department and returns it with who was in it. The employees
+    // themselves stay in the directory, and the departments inside it move
+    // up to where it was.
+    pub fn delete_department(&mut self, department: &str) -> Result<Department, DirectoryError> {
+        let key = name_key(department);
+        let deleted = self
+            .departments
+            .remove(&key)
+            .ok_or_else(|| DirectoryError::NoSuchDepartment(department.to_string()))?;
+        for child in self.departments.values_mut() {
+            if child.parent.as_ref() == Some(&key) {
+                child.parent = deleted.parent.clone();
+            }
+        }
+        Ok(deleted)
+    }
+
+    // Creates an empty department, at the top level or inside `parent`.
+    pub fn create_department(&mut self, name: &str, parent: Option<&str>) -> Result<(), DirectoryError> {
+        if let Some(existing) = self.department(name) {
+            return Err(DirectoryError::DepartmentExists(existing.name.clone()));
+        }
+        let parent = match parent {
+            Some(parent) => match self.department(parent) {
+                Some(parent) => Some(name_key(&parent.name)),
+                None => return Err(DirectoryError::NoSuchDepartment(parent.to_string())),
+            },
+            None => None,
+        };
+        self.departments.insert(
+            name_key(name),
+            Department {
+                name: display_name(name),
+                members: Vec::new(),
+                parent,
+            },
+        );
+        Ok(())
+    }
+
+    // Moves `department`, with everything inside it, under `parent`, or to
+    // the top level for None.
+    pub fn set_parent(&mut self, department: &str, parent: Option<&str>) -> Result<(), DirectoryError> {
+        let key = name_key(department);
+        let Some(moved) = self.departments.get(&key) else {
+            return Err(DirectoryError::NoSuchDepartment(department.to_string()));
+        };
+        let parent_key = match parent {
+            Some(parent) => {
+                let Some(parent) = self.department(parent) else {
+                    return Err(DirectoryError::NoSuchDepartment(parent.to_string()));
+                };
+                // Walking up from the new parent must not run into the department itself.
+                let mut ancestor = Some(parent);
+                while let Some(current) = ancestor {
+                    if name_key(&current.name) == key {
+                        return Err(DirectoryError::DepartmentCycle {
+                            department: moved.name.clone(),
+                            parent: parent.name.clone(),
+                        });
+                    }
+                    ancestor = self.parent(current);
+                }
+                Some(name_key(&parent.name))
+            }
+            None => None,
+        };
+        self.departments.get_mut(&key).expect("checked above").parent = parent_key;
+        Ok(())
+    }
+
+    // Current value of one attribute, as it would be typed into `set`.
+    pub fn attribute(&self, id: EmployeeId, field: Field) -> Option<String> {
+        let employee = self.employees.get(&id)?;
+        match field {
+            Field::Name => Some(employee.name.clone()),
+            Field::Title => employee.title.clone(),
+            Field::Email => employee.email.clone(),
+            Field::StartDate => employee.start_date.clone(),
+            Field::Manager => employee.manager.map(|manager| format!("#{}", manager)),
+        }
+    }
+
+    // Sets one attribute and returns what it was before. `None` clears it,
+    // which every field except the name allows.
+    pub fn set_attribute(&mut self, id: EmployeeId, field: Field, value: Option<&str>) -> Result<Option<String>, DirectoryError> {
+        if !self.employees.contains_key(&id) {
+            return Err(DirectoryError::NoSuchEmployee(format!("#{}", id)));
+        }
+        let previous = self.attribute(id, field);
+
+        let invalid = |reason| DirectoryError::InvalidValue {
+            field,
+            value: value.unwrap_or_default().to_string(),
+            reason,
+        };
+        let manager = match (field, value) {
+            (Field::Manager, Some(reference)) => {
+                let manager = self.resolve(reference)?;
+                if manager == id {
+                    return Err(invalid("nobody can be their own manager"));
+                }
+                if self.management_chain(manager).contains(&id) {
+                    return Err(DirectoryError::ManagementCycle {
+                        employee: format!("{} (#{})", self.name_of(id), id),
+                        manager: format!("{} (#{})", self.name_of(manager), manager),
+                    });
+                }
+                Some(manager)
+            }
+            _ => None,
+        };
+        match (field, value) {
+            (Field::Name, None) => return Err(invalid("every employee needs a name")),
+            (Field::Email, Some(email)) if !is_valid_email(email) => {
+                return Err(invalid("expected something like name@example.com"));
+            }
+            (Field::StartDate, Some(date)) if !is_valid_date(date) => {
+                return Err(invalid("expected a date like 2024-03-01"));
+            }
+            _ => {}
+        }
+
+        let employee = self.employees.get_mut(&id).expect("checked above");
+        let value = value.map(|value| value.to_string());
+        match field {
+            Field::Name => employee.name = display_name(&value.unwrap_or_default()),
+            Field::Title => employee.title = value,
+            Field::Email => employee.email = value,
+            Field::StartDate => employee.start_date = value,
+            Field::Manager => employee.manager = manager,
+        }
+        Ok(previous)
+    }
+}
+
+// `#3` or `3`, as opposed to a name.
+pub fn is_id_reference(reference: &str) -> bool {
+    let digits = reference.strip_prefix('#').unwrap_or(reference);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((user, domain)) => !user.is_empty() && domain.contains('.') && !email.contains(char::is_whitespace),
+        None => false,
+    }
+}
+
+// YYYY-MM-DD with a real month and a day that exists in that month.
+pub fn is_valid_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<u32>(), month.parse::<u32>(), day.parse::<u32>()) else {
+        return false;
+    };
+    let is_leap_year = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days_in_month).contains(&day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory_with(names: &[&str]) -> (Directory, Vec<EmployeeId>) {
+        let mut directory = Directory::new();
+        let ids = names.iter().map(|name| directory.resolve_or_create(name).unwrap().0).collect();
+        (directory, ids)
+    }
+
+    #[test]
+    fn nobody_can_manage_themselves() {
+        let (mut directory, ids) = directory_with(&["Sally"]);
+        let error = directory.set_attribute(ids[0], Field::Manager, Some("Sally")).unwrap_err();
+        assert!(matches!(error, DirectoryError::InvalidValue { field: Field::Manager, .. }), "{:?}", error);
+        assert_eq!(directory.attribute(ids[0], Field::Manager), None);
+    }
+
+    #[test]
+    fn managing_your_own_manager_is_a_cycle() {
+        let (mut directory, ids) = directory_with(&["Sally", "Amir"]);
+        directory.set_attribute(ids[1], Field::Manager, Some("Sally")).unwrap();
+        let error = directory.set_attribute(ids[0], Field::Manager, Some("Amir")).unwrap_err();
+        assert_eq!(error.code(), "management_cycle");
+        assert_eq!(directory.attribute(ids[0], Field::Manager), None);
+    }
+
+    #[test]
+    fn cycles_further_up_the_chain_are_caught_too() {
+        let (mut directory, ids) = directory_with(&["Sally", "Amir", "Bo"]);
+        directory.set_attribute(ids[1], Field::Manager, Some("Sally")).unwrap();
+        directory.set_attribute(ids[2], Field::Manager, Some("Amir")).unwrap();
+        assert_eq!(directory.set_attribute(ids[0], Field::Manager, Some("#3")).unwrap_err().code(), "management_cycle");
+        // Moving somebody further down is not a cycle.
+        assert_eq!(directory.set_attribute(ids[2], Field::Manager, Some("Sally")).unwrap(), Some("#2".to_string()));
+        assert_eq!(directory.management_chain(ids[2]), [ids[0]]);
+    }
+
+    #[test]
+    fn an_id_nobody_has_is_not_a_new_name() {
+        let (mut directory, _) = directory_with(&["Sally"]);
+        assert_eq!(directory.resolve_or_create("5"), Err(DirectoryError::NoSuchEmployee("#5".to_string())));
+        assert_eq!(directory.resolve_or_create("#5"), Err(DirectoryError::NoSuchEmployee("#5".to_string())));
+        assert_eq!(directory.employees().count(), 1);
+        assert_eq!(directory.resolve_or_create("5 Stars"), Ok((2, true)));
+    }
+}
@@ -0,0 +1,278 @@
+// The line editor behind the department prompt.
+//
+// Lines can be edited with the arrow keys, earlier commands come back with
+// up and down, and they are kept in `~/.untitled_history` for next time.
+// Tab completes whatever fits where the cursor is: a command name as the
+// first word, then the keywords, departments, employees, fields or columns
+// the command expects in that position. Names with spaces are completed
+// already quoted.
+
+use std::env;
+use std::path::PathBuf;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use super::command::{quote, Column, COMMAND_HELP};
+use super::directory::{Directory, Field};
+use super::names::name_key;
+
+pub const HISTORY_FILE: &str = ".untitled_history";
+
+pub type DirectoryEditor = Editor<DirectoryHelper, DefaultHistory>;
+
+// Where the history is kept, if there is a home directory to keep it in.
+pub fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+// An editor that completes against the directory and remembers what was
+// typed in earlier sessions.
+pub fn open_editor() -> rustyline::Result<DirectoryEditor> {
+    let mut editor = DirectoryEditor::new()?;
+    editor.set_helper(Some(DirectoryHelper::default()));
+    // There is no history the first time round.
+    if let Some(path) = history_path() {
+        let _ = editor.load_history(&path);
+    }
+    Ok(editor)
+}
+
+pub fn save_history(editor: &mut DirectoryEditor) -> rustyline::Result<()> {
+    match history_path() {
+        Some(path) => editor.save_history(&path),
+        None => Ok(()),
+    }
+}
+
+// What the word under the cursor can be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Command,
+    Keyword(&'static [&'static str]),
+    Department,
+    Employee,
+    // A department or an employee.
+    Name,
+    Field,
+    Column,
+    // A new name, a path or a value, nothing to suggest.
+    Anything,
+}
+
+// The names completion picks from, copied out of the directory before each
+// prompt so the editor doesn't have to hold on to it.
+#[derive(Debug, Default)]
+pub struct DirectoryHelper {
+    departments: Vec<String>,
+    employees: Vec<String>,
+}
+
+impl DirectoryHelper {
+    pub fn refresh(&mut self, directory: &Directory) {
+        self.departments = directory.departments().map(|department| department.name.clone()).collect();
+        self.employees = directory.employees().map(|employee| employee.name.clone()).collect();
+        self.employees.sort();
+        self.employees.dedup();
+    }
+
+    fn candidates(&self, slot: Slot) -> Vec<String> {
+        let words = |words: &[&str]| words.iter().map(|word| word.to_string()).collect();
+        match slot {
+            Slot::Command => {
+                let mut commands: Vec<String> = COMMAND_HELP
+                    .iter()
+                    .filter_map(|(usage, _)| usage.split_whitespace().next())
+                    .map(|command| command.to_string())
+                    .collect();
+                commands.dedup();
+                commands
+            }
+            Slot::Keyword(keywords) => words(keywords),
+            Slot::Department => self.departments.clone(),
+            Slot::Employee => self.employees.clone(),
+            Slot::Name => [self.departments.clone(), self.employees.clone()].concat(),
+            Slot::Field => Field::ALL.iter().map(|field| field.as_str().to_string()).collect(),
+            Slot::Column => Column::ALL.iter().map(|column| column.as_str().to_string()).collect(),
+            Slot::Anything => Vec::new(),
+        }
+    }
+}
+
+impl Completer for DirectoryHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (words, mut start) = split_words(&line[..pos]);
+        let slot = slot_for(&words);
+        let mut typed = &line[start..pos];
+        // Columns are typed as a list, only the one after the last comma is completed.
+        if slot == Slot::Column
+            && let Some(comma) = typed.rfind(',')
+        {
+            start += comma + 1;
+            typed = &line[start..pos];
+        }
+
+        let typed_key = name_key(typed.trim_start_matches(['"', '\'']));
+        let candidates = self
+            .candidates(slot)
+            .into_iter()
+            .filter(|candidate| name_key(candidate).starts_with(&typed_key))
+            .map(|candidate| Pair {
+                replacement: match slot {
+                    Slot::Department | Slot::Employee | Slot::Name => quote(&candidate),
+                    _ => candidate.clone(),
+                },
+                display: candidate,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for DirectoryHelper {
+    type Hint = String;
+}
+
+impl Highlighter for DirectoryHelper {}
+
+impl Validator for DirectoryHelper {}
+
+impl Helper for DirectoryHelper {}
+
+// Splits the text before the cursor into the words already finished, with
+// their quotes taken off, and the byte offset where the word being typed
+// starts.
+fn split_words(text: &str) -> (Vec<String>, usize) {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut start = 0;
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                start = index + c.len_utf8();
+            }
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (Some(open), c) if c == open => quote = None,
+            (_, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    (words, start)
+}
+
+// Works out what goes in the next position from the words before it, going
+// by each command's usage.
+fn slot_for(words: &[String]) -> Slot {
+    use Slot::*;
+
+    let Some((command, arguments)) = words.split_first() else {
+        return Command;
+    };
+    // Everything after `with` is a column.
+    if arguments.iter().any(|argument| argument.eq_ignore_ascii_case("with")) {
+        return Column;
+    }
+    let slots: &[Slot] = match command.to_ascii_lowercase().as_str() {
+        "add" => &[Employee, Keyword(&["to"]), Department],
+        "list" if arguments.get(1).is_some_and(|word| word.eq_ignore_ascii_case("recursive")) => {
+            &[Department, Anything, Keyword(&["with"])]
+        }
+        "list" => &[Department, Keyword(&["recursive", "with"])],
+        "list-all" => &[Keyword(&["with"])],
+        "remove" => &[Employee, Keyword(&["from"]), Department],
+        "move" => &[Employee, Keyword(&["from"]), Department, Keyword(&["to"]), Department],
+        "rename-department" => &[Department, Anything],
+        "delete-department" => &[Department],
+        "create-department" => &[Anything, Keyword(&["under"]), Department],
+        "reparent" => &[Department, Keyword(&["under"]), Department],
+        "set" => &[Employee, Field],
+        "unset" => &[Employee, Field],
+        "show" | "reports" | "chain" => &[Employee],
+        "assign" => &[Employee, Keyword(&["manager"]), Employee],
+        "import" => &[Anything, Keyword(&["dry-run"])],
+        "login" => &[Anything],
+        "audit" => &[Name, Keyword(&["since"])],
+        _ => &[],
+    };
+    slots.get(arguments.len()).copied().unwrap_or(Anything)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(text: &str) -> Slot {
+        slot_for(&split_words(text).0)
+    }
+
+    #[test]
+    fn finished_words_lose_their_quotes() {
+        assert_eq!(split_words("add \"Mary Ann\" to "), (vec!["add".to_string(), "Mary Ann".to_string(), "to".to_string()], 18));
+        assert_eq!(split_words("move 'Bo' from Sa"), (vec!["move".to_string(), "Bo".to_string(), "from".to_string()], 15));
+        assert_eq!(split_words(""), (vec![], 0));
+    }
+
+    #[test]
+    fn a_word_inside_an_open_quote_is_still_being_typed() {
+        // The space inside the quote doesn't end the word, it starts at the quote.
+        assert_eq!(split_words("add \"Mary A"), (vec!["add".to_string()], 4));
+        assert_eq!(slot("add \"Mary A"), Slot::Employee);
+        assert_eq!(slot("add \"Mary Ann\" "), Slot::Keyword(&["to"]));
+    }
+
+    #[test]
+    fn slots_follow_the_usage() {
+        assert_eq!(slot(""), Slot::Command);
+        assert_eq!(slot("ad"), Slot::Command);
+        assert_eq!(slot("add Sally to "), Slot::Department);
+        assert_eq!(slot("move Sally from Sales to "), Slot::Department);
+        assert_eq!(slot("set Sally "), Slot::Field);
+        assert_eq!(slot("list Sales recursive "), Slot::Keyword(&["with"]));
+        assert_eq!(slot("list-all with name,"), Slot::Column);
+        assert_eq!(slot("LIST Sales "), Slot::Keyword(&["recursive", "with"]));
+        assert_eq!(slot("rename-department Sales "), Slot::Anything);
+        assert_eq!(slot("delete-department Sales extra "), Slot::Anything);
+        assert_eq!(slot("frobnicate "), Slot::Anything);
+    }
+
+    #[test]
+    fn names_are_completed_quoted_from_an_open_quote() {
+        let mut directory = Directory::new();
+        for name in ["Mary Ann", "Mark", "Bo"] {
+            directory.resolve_or_create(name).unwrap();
+        }
+        let mut helper = DirectoryHelper::default();
+        helper.refresh(&directory);
+        let history = DefaultHistory::new();
+        let context = Context::new(&history);
+
+        let line = "add \"mary a";
+        let (start, candidates) = helper.complete(line, line.len(), &context).unwrap();
+        assert_eq!(start, 4);
+        let replacements: Vec<&str> = candidates.iter().map(|pair| pair.replacement.as_str()).collect();
+        assert_eq!(replacements, ["\"Mary Ann\""]);
+
+        let line = "add Mar";
+        let (start, candidates) = helper.complete(line, line.len(), &context).unwrap();
+        assert_eq!(start, 4);
+        let replacements: Vec<&str> = candidates.iter().map(|pair| pair.replacement.as_str()).collect();
+        assert_eq!(replacements, ["Mark", "\"Mary Ann\""]);
+    }
+}
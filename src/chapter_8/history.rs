@@ -0,0 +1,70 @@
+// Undo and redo for the department prompt.
+//
+// Every command that changes the directory is recorded together with the
+// directory as it was right before and right after the command ran. Undoing
+// puts the "before" copy back and redoing puts the "after" copy back, so an
+// operation can always be reversed exactly, whatever the command did. The
+// directories are small enough that keeping whole copies is cheap, and only
+// the most recent `HISTORY_LIMIT` operations are kept.
+
+use super::directory::Directory;
+
+pub const HISTORY_LIMIT: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct Operation {
+    // The command that made the change, as it would be typed.
+    pub description: String,
+    before: Directory,
+    after: Directory,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    // Oldest first, so the next operation to undo is at the end.
+    done: Vec<Operation>,
+    // Operations that were undone, the next one to redo at the end.
+    undone: Vec<Operation>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    // Records a change. Anything that was undone can't be redone anymore
+    // once something new has happened.
+    pub fn record(&mut self, description: String, before: Directory, after: Directory) {
+        self.undone.clear();
+        self.done.push(Operation { description, before, after });
+        if self.done.len() > HISTORY_LIMIT {
+            self.done.remove(0);
+        }
+    }
+
+    // Puts `directory` back the way it was before the last operation and
+    // returns that operation, or None if there is nothing to undo.
+    pub fn undo(&mut self, directory: &mut Directory) -> Option<&Operation> {
+        let operation = self.done.pop()?;
+        directory.restore(operation.before.clone());
+        self.undone.push(operation);
+        self.undone.last()
+    }
+
+    pub fn redo(&mut self, directory: &mut Directory) -> Option<&Operation> {
+        let operation = self.undone.pop()?;
+        directory.restore(operation.after.clone());
+        self.done.push(operation);
+        self.done.last()
+    }
+
+    // Operations that can be undone, oldest first.
+    pub fn done(&self) -> &[Operation] {
+        &self.done
+    }
+
+    // Operations that can be redone, the next one to redo last.
+    pub fn undone(&self) -> &[Operation] {
+        &self.undone
+    }
+}
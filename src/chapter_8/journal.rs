@@ -0,0 +1,232 @@
+// Append-only journal of directory changes.
+//
+// Every command that changes the directory is written to the journal, and
+// flushed to disk, before it is applied. The journal sits next to the
+// snapshot written by `persistence` (`company_directory.json.journal`) and
+// holds one JSON record per line:
+//
+// {"seq":7,"command":"add \"Mary Ann\" to Sales"}
+//
+// Commands that depend on something outside the directory, like `import`
+// reading a file, are written together with the `steps` they turned into,
+// and replaying runs those steps instead of the original command:
+//
+// {"seq":8,"command":"import roster.csv","steps":["add Bob to Sales","set Bob title Rep"]}
+//
+// `seq` counts up forever. The snapshot remembers the last `seq` folded into
+// it, so on startup the program loads the snapshot and replays only the
+// newer records. Replaying runs the same commands again on the same starting
+// directory, so it ends up in the same state as before the program stopped.
+//
+// A crash can leave the last record half written. A final line without its
+// newline is treated as never written: it is dropped and cut off the file so
+// the next record starts on a clean line. A broken record anywhere else
+// means the file was damaged and loading stops instead of guessing.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub seq: u64,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    last_seq: u64,
+    // Records written since the journal was last folded into a snapshot.
+    pending: usize,
+}
+
+// What `Journal::open` found in an existing journal.
+#[derive(Debug, Default)]
+pub struct Recovered {
+    // Records newer than the snapshot, oldest first.
+    pub records: Vec<JournalRecord>,
+    // Whether a half written record was dropped from the end.
+    pub truncated: bool,
+}
+
+impl Journal {
+    pub fn path_for(snapshot_path: &Path) -> PathBuf {
+        let mut file_name = snapshot_path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".journal");
+        snapshot_path.with_file_name(file_name)
+    }
+
+    // Opens the journal at `path`, creating it if needed, and returns the
+    // records that come after `snapshot_seq`.
+    pub fn open(path: &Path, snapshot_seq: u64) -> io::Result<(Journal, Recovered)> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error),
+        };
+
+        // Everything up to the last newline is made of whole records.
+        let complete_length = contents.iter().rposition(|byte| *byte == b'\n').map_or(0, |index| index + 1);
+        let mut recovered = Recovered {
+            records: Vec::new(),
+            truncated: complete_length < contents.len(),
+        };
+        let mut last_seq = snapshot_seq;
+        for (index, line) in contents[..complete_length].split(|byte| *byte == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let record: JournalRecord = serde_json::from_slice(line).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is damaged at line {}: {}", path.display(), index + 1, error),
+                )
+            })?;
+            if record.seq > last_seq {
+                last_seq = record.seq;
+                recovered.records.push(record);
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        if recovered.truncated {
+            file.set_len(complete_length as u64)?;
+            file.sync_all()?;
+        }
+        let journal = Journal {
+            file,
+            last_seq,
+            pending: recovered.records.len(),
+        };
+        Ok((journal, recovered))
+    }
+
+    // Writes one command and waits for it to reach the disk.
+    pub fn append(&mut self, command: &str, steps: &[String]) -> io::Result<u64> {
+        let record = JournalRecord {
+            seq: self.last_seq + 1,
+            command: command.to_string(),
+            steps: steps.to_vec(),
+        };
+        let mut line = serde_json::to_string(&record).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        line.push('\n');
+        // One write for the whole line, so a crash can only cut off the end of it.
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        self.last_seq = record.seq;
+        self.pending += 1;
+        Ok(record.seq)
+    }
+
+    // Empties the journal once everything in it is safe in a snapshot.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.pending = 0;
+        Ok(())
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commands(recovered: &Recovered) -> Vec<&str> {
+        recovered.records.iter().map(|record| record.command.as_str()).collect()
+    }
+
+    // A journal at a fresh path with `lines` already written to it.
+    fn journal_with(lines: &[&str]) -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("directory.json.journal");
+        let (mut journal, _) = Journal::open(&path, 0).unwrap();
+        for line in lines {
+            journal.append(line, &[]).unwrap();
+        }
+        (dir, path)
+    }
+
+    #[test]
+    fn records_come_back_in_order() {
+        let (_dir, path) = journal_with(&["add Sally to Sales", "add Amir to Sales"]);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"seq\":3,\"command\":\"import roster.csv\",\"steps\":[\"add Bob to Sales\"]}\n")
+            .unwrap();
+
+        let (journal, recovered) = Journal::open(&path, 0).unwrap();
+        assert_eq!(commands(&recovered), ["add Sally to Sales", "add Amir to Sales", "import roster.csv"]);
+        assert_eq!(recovered.records[2].steps, ["add Bob to Sales"]);
+        assert!(!recovered.truncated);
+        assert_eq!((journal.last_seq(), journal.pending()), (3, 3));
+    }
+
+    #[test]
+    fn records_already_in_the_snapshot_are_skipped() {
+        let (_dir, path) = journal_with(&["add Sally to Sales", "add Amir to Sales", "add Bob to Sales"]);
+        let (mut journal, recovered) = Journal::open(&path, 2).unwrap();
+        assert_eq!(commands(&recovered), ["add Bob to Sales"]);
+        assert_eq!(journal.append("add Bea to Sales", &[]).unwrap(), 4);
+    }
+
+    #[test]
+    fn a_half_written_last_record_is_dropped_and_cut_off() {
+        let (_dir, path) = journal_with(&["add Sally to Sales", "add Amir to Sales"]);
+        let whole = fs::read(&path).unwrap();
+        // Cut the second record off in the middle, like a crash while writing it.
+        let first_length = whole.iter().position(|byte| *byte == b'\n').unwrap() + 1;
+        fs::write(&path, &whole[..first_length + 10]).unwrap();
+
+        let (mut journal, recovered) = Journal::open(&path, 0).unwrap();
+        assert!(recovered.truncated);
+        assert_eq!(commands(&recovered), ["add Sally to Sales"]);
+        assert_eq!(fs::read(&path).unwrap(), &whole[..first_length]);
+
+        // The next record starts on a clean line and gets the next seq.
+        assert_eq!(journal.append("add Bob to Sales", &[]).unwrap(), 2);
+        let (_, recovered) = Journal::open(&path, 0).unwrap();
+        assert!(!recovered.truncated);
+        assert_eq!(commands(&recovered), ["add Sally to Sales", "add Bob to Sales"]);
+    }
+
+    #[test]
+    fn a_damaged_record_in_the_middle_stops_loading() {
+        let (_dir, path) = journal_with(&["add Sally to Sales"]);
+        let mut contents = fs::read_to_string(&path).unwrap();
+        contents.push_str("{\"seq\":2,\"comm\n");
+        contents.push_str("{\"seq\":3,\"command\":\"add Bob to Sales\"}\n");
+        fs::write(&path, &contents).unwrap();
+
+        let error = Journal::open(&path, 0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 2"));
+        // Nothing was cut off, the file is left for somebody to look at.
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+    }
+
+    #[test]
+    fn clearing_empties_the_file_but_keeps_counting() {
+        let (_dir, path) = journal_with(&[]);
+        let (mut journal, _) = Journal::open(&path, 0).unwrap();
+        journal.append("add Sally to Sales", &[]).unwrap();
+        journal.clear().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        assert_eq!((journal.last_seq(), journal.pending()), (1, 0));
+        assert_eq!(journal.append("add Amir to Sales", &[]).unwrap(), 2);
+    }
+}
@@ -1,2 +1,20 @@
+pub mod access;
+pub mod audit;
+pub mod command;
 pub mod common_collections;
-pub use common_collections::*;
\ No newline at end of file
+pub mod csv;
+pub mod directory;
+pub mod editor;
+pub mod history;
+pub mod journal;
+pub mod names;
+pub mod output;
+pub mod persistence;
+pub mod repl;
+pub mod roster;
+pub mod script;
+pub mod search;
+pub mod server;
+pub mod session;
+pub mod store;
+pub use common_collections::*;
@@ -0,0 +1,58 @@
+// Turning names into lookup keys.
+//
+// People type the same name in different ways: `Engineering`, `engineering`
+// or `  Engineering ` all mean one department, and `STRASSE` and `Straße`
+// are the same word. Lookups go through `name_key`, which trims the name,
+// squashes runs of whitespace into one space and applies Unicode case
+// folding, so all of those end up as the same key. The name as it was first
+// typed is still what gets shown.
+
+use caseless::default_case_fold_str;
+use unicode_normalization::UnicodeNormalization;
+
+pub fn name_key(name: &str) -> String {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    // Decomposing before folding is what Unicode asks for when comparing
+    // without case, so é typed as one or two code points matches. The key is
+    // composed again afterwards so é is still one character in it.
+    let decomposed: String = words.join(" ").nfd().collect();
+    default_case_fold_str(&decomposed).nfc().collect()
+}
+
+// The spelling to show for a newly typed name: trimmed, but otherwise as typed.
+pub fn display_name(name: &str) -> String {
+    name.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sharp_s_folds_to_double_s() {
+        assert_eq!(name_key("Straße"), name_key("STRASSE"));
+        assert_eq!(name_key("Straße"), "strasse");
+    }
+
+    #[test]
+    fn composed_and_decomposed_accents_are_the_same_key() {
+        let composed = "Ren\u{e9}e";
+        let decomposed = "Rene\u{301}e";
+        assert_ne!(composed, decomposed);
+        assert_eq!(name_key(composed), name_key(decomposed));
+        assert_eq!(name_key(decomposed), "ren\u{e9}e");
+        assert_eq!(name_key("REN\u{c9}E"), name_key(decomposed));
+    }
+
+    #[test]
+    fn whitespace_is_trimmed_and_collapsed() {
+        assert_eq!(name_key("  Mary \t  Ann\n"), "mary ann");
+        assert_eq!(name_key("Mary\u{a0}Ann"), "mary ann");
+        assert_eq!(name_key("   "), "");
+    }
+
+    #[test]
+    fn names_are_shown_as_typed_but_trimmed() {
+        assert_eq!(display_name("  Mary  Ann "), "Mary  Ann");
+    }
+}
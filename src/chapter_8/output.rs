@@ -0,0 +1,130 @@
+// How the results of commands are shown.
+//
+// `text` is what the prompt has always printed. `json` and `tsv` are for
+// other programs reading the output, and their shape doesn't change between
+// versions:
+//
+// - json prints one object per command, on a single line. Listings give
+//   `{"records":[{"id":1,"name":"Amir","departments":["Sales"]}]}`, with
+//   missing values as null. Anything else gives `{"messages":["..."]}` and
+//   errors give `{"error":{"code":"no_such_department","message":"..."}}`,
+//   plus the `line` when the command came from a script.
+// - tsv prints a listing as a header row and one row per record, with tabs,
+//   newlines and backslashes in values escaped as `\t`, `\n` and `\\`, and
+//   lists of names separated by commas (a comma inside a name is `\,`).
+//   Errors are `error<TAB>code<TAB>line<TAB>message` rows, other messages
+//   are printed as they are.
+//
+// Listings keep their human readable lines next to the records, so the text
+// format doesn't change at all.
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::common_collections::CommandError;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Tsv,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 3] = [OutputFormat::Text, OutputFormat::Json, OutputFormat::Tsv];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Tsv => "tsv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        OutputFormat::ALL.into_iter().find(|format| format.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+// What a command produced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    // Messages for people, shown the same way whatever the format.
+    Lines(Vec<String>),
+    // Rows of records under named columns, and how they read as text.
+    Listing { text: Vec<String>, columns: Vec<String>, rows: Vec<Vec<Value>> },
+}
+
+impl Output {
+    pub fn render(&self, format: OutputFormat) -> Vec<String> {
+        match (self, format) {
+            (Output::Lines(lines), OutputFormat::Text | OutputFormat::Tsv) => lines.clone(),
+            (Output::Listing { text, .. }, OutputFormat::Text) => text.clone(),
+            // Nothing to say, like after an empty line.
+            (Output::Lines(lines), OutputFormat::Json) if lines.is_empty() => Vec::new(),
+            (Output::Lines(lines), OutputFormat::Json) => vec![json!({ "messages": lines }).to_string()],
+            (Output::Listing { columns, rows, .. }, OutputFormat::Json) => {
+                let records: Vec<Value> = rows
+                    .iter()
+                    .map(|row| {
+                        let record: Map<String, Value> = columns.iter().cloned().zip(row.iter().cloned()).collect();
+                        Value::Object(record)
+                    })
+                    .collect();
+                vec![json!({ "records": records }).to_string()]
+            }
+            (Output::Listing { columns, rows, .. }, OutputFormat::Tsv) => {
+                let header: Vec<String> = columns.iter().map(|column| tsv_field(column)).collect();
+                std::iter::once(header.join("\t"))
+                    .chain(rows.iter().map(|row| {
+                        let fields: Vec<String> = row.iter().map(tsv_value).collect();
+                        fields.join("\t")
+                    }))
+                    .collect()
+            }
+        }
+    }
+}
+
+// An error as a record, with the script line it came from if there is one.
+// Text errors are worded by whoever shows them, so this is only for the
+// structured formats.
+pub fn render_error(error: &CommandError, line: Option<usize>, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json => {
+            let mut record = json!({ "code": error.code(), "message": error.to_string() });
+            if let Some(line) = line {
+                record["line"] = json!(line);
+            }
+            json!({ "error": record }).to_string()
+        }
+        OutputFormat::Text | OutputFormat::Tsv => {
+            let line = line.map(|line| line.to_string()).unwrap_or_default();
+            format!("error\t{}\t{}\t{}", error.code(), line, tsv_field(&error.to_string()))
+        }
+    }
+}
+
+fn tsv_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => tsv_field(text),
+        Value::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| tsv_value(item).replace(',', "\\,"))
+                .collect();
+            items.join(",")
+        }
+        other => tsv_field(&other.to_string()),
+    }
+}
+
+fn tsv_field(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
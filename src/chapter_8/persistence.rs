@@ -0,0 +1,328 @@
+// Saving and loading the department directory.
+//
+// The directory is stored as a single JSON document:
+//
+// {
+//   "version": 4,
+//   "next_id": 3,
+//   "journal_seq": 12,
+//   "employees": [
+//     { "id": 1, "name": "Amir", "title": "Engineer" },
+//     { "id": 2, "name": "Sally", "email": "sally@example.com", "manager": 1 }
+//   ],
+//   "departments": {
+//     "Engineering": [1],
+//     "Platform": [2],
+//     "Sales": [2]
+//   },
+//   "parents": {
+//     "Platform": "Engineering"
+//   }
+// }
+//
+// Employee fields other than `id` and `name` are left out when they are not
+// set. `next_id` is the id the next new employee gets, so ids are never
+// handed out twice. Departments list their members by id. `journal_seq` is
+// the last journal record already included in this file, see `journal`.
+// `parents` names the department each sub-department sits in, departments
+// at the top level are left out of it.
+//
+// Version history:
+//   1 - `departments` mapped each department to a list of names.
+//   2 - employees became records with ids and optional attributes. A
+//       version 1 file is converted when it is loaded, with each distinct
+//       name becoming one employee.
+//   3 - added `journal_seq`. Older files have not seen any journal records,
+//       so it starts at 0 for them.
+//   4 - added `parents`. Older files have no sub-departments.
+//
+// `version` is the schema version of the file. Every time the shape of the
+// file changes, `SCHEMA_VERSION` gets bumped and `load_snapshot` learns how
+// to migrate the older versions forward, so files written by older builds
+// keep loading instead of breaking.
+//
+// Day to day changes go to the journal (see `journal`) and the snapshot is
+// only rewritten when the journal is compacted.
+//
+// Saving never writes to the data file directly. The new contents go to a
+// temporary file next to it which is then renamed over the old one, so a
+// crash halfway through a save leaves the previous file untouched.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::directory::{Directory, Employee, EmployeeId};
+use super::journal::{Journal, JournalRecord};
+
+pub const SCHEMA_VERSION: u32 = 4;
+pub const DEFAULT_DIRECTORY_PATH: &str = "company_directory.json";
+
+#[derive(Deserialize)]
+struct DirectoryFileV1 {
+    departments: BTreeMap<String, Vec<String>>,
+}
+
+// Versions 2 to 4 only differ by `journal_seq` and `parents`, which
+// default to 0 and nothing.
+#[derive(Serialize, Deserialize)]
+struct DirectoryFileV4 {
+    version: u32,
+    next_id: EmployeeId,
+    #[serde(default)]
+    journal_seq: u64,
+    employees: Vec<Employee>,
+    // BTreeMap so the departments come out sorted and the file diffs nicely.
+    departments: BTreeMap<String, Vec<EmployeeId>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    parents: BTreeMap<String, String>,
+}
+
+// Just enough of the file to find out which version wrote it.
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u32,
+}
+
+// A saved directory and how much of the journal it already contains.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub directory: Directory,
+    pub journal_seq: u64,
+    // Things that had to be fixed up while loading, like merged departments.
+    pub warnings: Vec<String>,
+}
+
+// Loads the snapshot at `path`. A missing file is not an error, it just
+// means nothing has been saved yet.
+pub fn load_snapshot(path: &Path) -> io::Result<Snapshot> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Snapshot::default()),
+        Err(error) => return Err(error),
+    };
+
+    let header: VersionHeader = serde_json::from_str(&contents).map_err(invalid_data)?;
+    match header.version {
+        1 => {
+            let file: DirectoryFileV1 = serde_json::from_str(&contents).map_err(invalid_data)?;
+            let hash_table: HashMap<String, Vec<String>> = file.departments.into_iter().collect();
+            let (directory, warnings) = Directory::from_department_names(&hash_table);
+            Ok(Snapshot {
+                directory,
+                journal_seq: 0,
+                warnings,
+            })
+        }
+        2..=4 => {
+            let file: DirectoryFileV4 = serde_json::from_str(&contents).map_err(invalid_data)?;
+            let (directory, warnings) =
+                Directory::from_parts(file.next_id, file.employees, file.departments, file.parents);
+            Ok(Snapshot {
+                directory,
+                journal_seq: file.journal_seq,
+                warnings,
+            })
+        }
+        version => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} uses schema version {}, but this program only understands up to version {}",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            ),
+        )),
+    }
+}
+
+// Writes the directory to `path` atomically.
+pub fn save_snapshot(path: &Path, directory: &Directory, journal_seq: u64) -> io::Result<()> {
+    let file = DirectoryFileV4 {
+        version: SCHEMA_VERSION,
+        next_id: directory.next_id(),
+        journal_seq,
+        employees: directory.employees().cloned().collect(),
+        departments: directory
+            .departments()
+            .map(|department| (department.name.clone(), department.members.clone()))
+            .collect(),
+        parents: directory
+            .departments()
+            .filter_map(|department| {
+                let parent = directory.parent(department)?;
+                Some((department.name.clone(), parent.name.clone()))
+            })
+            .collect(),
+    };
+    let mut contents = serde_json::to_string_pretty(&file).map_err(invalid_data)?;
+    contents.push('\n');
+    write_atomically(path, contents.as_bytes())
+}
+
+// The snapshot and journal of one directory on disk.
+#[derive(Debug)]
+pub struct Storage {
+    snapshot_path: PathBuf,
+    journal: Journal,
+}
+
+// What `Storage::open` read from disk.
+#[derive(Debug)]
+pub struct Opened {
+    pub storage: Storage,
+    pub directory: Directory,
+    // Journal records that still have to be replayed on top of `directory`.
+    pub records: Vec<JournalRecord>,
+    pub truncated: bool,
+    pub warnings: Vec<String>,
+}
+
+impl Storage {
+    pub fn open(snapshot_path: &Path) -> io::Result<Opened> {
+        let snapshot = load_snapshot(snapshot_path)?;
+        let (journal, recovered) = Journal::open(&Journal::path_for(snapshot_path), snapshot.journal_seq)?;
+        Ok(Opened {
+            storage: Storage {
+                snapshot_path: snapshot_path.to_path_buf(),
+                journal,
+            },
+            directory: snapshot.directory,
+            records: recovered.records,
+            truncated: recovered.truncated,
+            warnings: snapshot.warnings,
+        })
+    }
+
+    // Writes `command` to the journal, with the steps it expands to if it
+    // can't simply be run again. Call this before applying it.
+    pub fn log(&mut self, command: &str, steps: &[String]) -> io::Result<()> {
+        self.journal.append(command, steps).map(|_| ())
+    }
+
+    // Saves `directory` as the new snapshot and empties the journal. Returns
+    // how many journal records were folded in.
+    pub fn compact(&mut self, directory: &Directory) -> io::Result<usize> {
+        let folded = self.journal.pending();
+        // If we crash between these two steps the snapshot already knows it
+        // includes every record, so they are skipped instead of replayed twice.
+        save_snapshot(&self.snapshot_path, directory, self.journal.last_seq())?;
+        self.journal.clear()?;
+        Ok(folded)
+    }
+
+    pub fn snapshot_path(&self) -> &Path {
+        &self.snapshot_path
+    }
+}
+
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp_path = temp_path_for(path);
+    let result = (|| {
+        let mut temp_file = fs::File::create(&temp_path)?;
+        temp_file.write_all(contents)?;
+        temp_file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+fn invalid_data(error: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chapter_8::directory::Field;
+
+    fn load(contents: &str) -> Snapshot {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("directory.json");
+        fs::write(&path, contents).unwrap();
+        load_snapshot(&path).unwrap()
+    }
+
+    fn members(directory: &Directory, department: &str) -> Vec<String> {
+        let department = directory.department(department).expect("no such department");
+        department.members.iter().map(|id| directory.name_of(*id)).collect()
+    }
+
+    #[test]
+    fn version_1_files_get_one_employee_per_name() {
+        let snapshot = load(r#"{"version": 1, "departments": {"Sales": ["Sally", "Bo"], "Engineering": ["Sally"]}}"#);
+        let directory = &snapshot.directory;
+        assert_eq!(snapshot.journal_seq, 0);
+        // Ids are handed out in name order.
+        assert_eq!(directory.resolve("Bo"), Ok(1));
+        assert_eq!(directory.resolve("Sally"), Ok(2));
+        assert_eq!(directory.employees().count(), 2);
+        assert_eq!(directory.next_id(), 3);
+        assert_eq!(members(directory, "Sales"), ["Sally", "Bo"]);
+        assert_eq!(members(directory, "Engineering"), ["Sally"]);
+        assert!(directory.departments().all(|department| directory.parent(department).is_none()));
+    }
+
+    #[test]
+    fn version_2_files_start_before_the_journal_and_without_parents() {
+        let snapshot = load(
+            r#"{"version": 2, "next_id": 2, "employees": [{"id": 1, "name": "Sally", "title": "Boss"}],
+                "departments": {"Sales": [1]}}"#,
+        );
+        assert_eq!(snapshot.journal_seq, 0);
+        assert_eq!(snapshot.directory.attribute(1, Field::Title).as_deref(), Some("Boss"));
+        let sales = snapshot.directory.department("Sales").unwrap();
+        assert!(snapshot.directory.parent(sales).is_none());
+    }
+
+    #[test]
+    fn saving_keeps_every_attribute_parents_and_the_journal_position() {
+        let mut directory = Directory::new();
+        let (sally, _) = directory.resolve_or_create("Sally").unwrap();
+        let (amir, _) = directory.resolve_or_create("Amir").unwrap();
+        directory.add_member("Engineering", sally).unwrap();
+        directory.create_department("Platform", Some("Engineering")).unwrap();
+        directory.add_member("Platform", amir).unwrap();
+        directory.set_attribute(amir, Field::Title, Some("Engineer")).unwrap();
+        directory.set_attribute(amir, Field::Email, Some("amir@example.com")).unwrap();
+        directory.set_attribute(amir, Field::StartDate, Some("2024-03-01")).unwrap();
+        directory.set_attribute(amir, Field::Manager, Some("Sally")).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("directory.json");
+        save_snapshot(&path, &directory, 12).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains(&format!("\"version\": {}", SCHEMA_VERSION)), "{}", saved);
+
+        let snapshot = load_snapshot(&path).unwrap();
+        assert_eq!(snapshot.journal_seq, 12);
+        assert!(snapshot.warnings.is_empty());
+        let loaded = &snapshot.directory;
+        assert_eq!(loaded.employee(amir), directory.employee(amir));
+        assert_eq!(loaded.employee(sally), directory.employee(sally));
+        assert_eq!(loaded.next_id(), directory.next_id());
+        let platform = loaded.department("Platform").unwrap();
+        assert_eq!(loaded.parent(platform).map(|parent| parent.name.as_str()), Some("Engineering"));
+        assert_eq!(members(loaded, "Platform"), ["Amir"]);
+    }
+
+    #[test]
+    fn files_from_newer_versions_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("directory.json");
+        fs::write(&path, format!(r#"{{"version": {}}}"#, SCHEMA_VERSION + 1)).unwrap();
+        let error = load_snapshot(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,119 @@
+// The interactive department prompt.
+//
+// `quit`, `exit`, `q` or Ctrl-D end it. Ctrl-C at the prompt throws away the
+// line being typed, like in a shell. While `import` is running it stops the
+// import instead of the whole program: nothing is changed and the prompt
+// comes back. `import` is the only command that checks, the others are
+// quick and just finish.
+//
+// Leaving a directory on disk never asks, its changes are safe in the
+// journal and get folded into the snapshot on the way out. A session that
+// only lives in memory loses its changes, so leaving one with changes asks
+// first.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rustyline::error::ReadlineError;
+
+use super::common_collections::{handle_command, Outcome};
+use super::editor::{open_editor, save_history, DirectoryEditor};
+use super::session::Session;
+
+// `{user}` is replaced with whoever is logged in, or nothing.
+pub const DEFAULT_PROMPT: &str = "department ({user}:> ";
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Makes Ctrl-C set a flag for long commands to check, instead of ending
+// the program.
+pub fn install_interrupt_handler() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+}
+
+// Whether Ctrl-C was pressed since the current command started. Only
+// `import` looks.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// Runs the prompt until the user leaves, showing `prompt` (see
+// `DEFAULT_PROMPT`) before every line. Fails only if the terminal can't be
+// read.
+pub fn run_repl(session: &mut Session, prompt: &str) -> rustyline::Result<()> {
+    let mut editor = open_editor()?;
+    let result = prompt_loop(&mut editor, session, prompt);
+    if let Err(error) = save_history(&mut editor) {
+        eprintln!("Could not save the command history: {}", error);
+    }
+    result
+}
+
+fn prompt_loop(editor: &mut DirectoryEditor, session: &mut Session, prompt: &str) -> rustyline::Result<()> {
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(&session.directory);
+        }
+        let user = session.access.user().map(|user| user.name.as_str()).unwrap_or("");
+        let line = match editor.readline(&prompt.replace("{user}", user)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => {
+                if confirm_quit(editor, session) {
+                    return Ok(());
+                }
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        // A Ctrl-C from before this command doesn't count against it.
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        if handle_command(&line, session) == Outcome::Quit && confirm_quit(editor, session) {
+            return Ok(());
+        }
+    }
+}
+
+// Whether to really leave. Only asks when the changes would be lost, see
+// `will_lose_changes`. Ctrl-C means no, and at the end of the input there
+// is nobody left to answer, so that means yes.
+fn confirm_quit(editor: &mut DirectoryEditor, session: &Session) -> bool {
+    if !will_lose_changes(session) {
+        return true;
+    }
+    match editor.readline("The changes in this session were never saved and will be lost. Quit anyway? [y/N] ") {
+        Ok(answer) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(ReadlineError::Interrupted) => false,
+        Err(_) => true,
+    }
+}
+
+// With storage, leaving saves everything, so only a session in memory can
+// lose anything.
+fn will_lose_changes(session: &Session) -> bool {
+    session.dirty && session.storage.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chapter_8::common_collections::run_line;
+    use crate::chapter_8::directory::Directory;
+
+    #[test]
+    fn only_changes_in_memory_can_be_lost() {
+        let mut session = Session::new(Directory::new());
+        assert!(!will_lose_changes(&session));
+        run_line("add Sally to Sales", &mut session).unwrap();
+        assert!(will_lose_changes(&session));
+
+        let dir = tempfile::tempdir().unwrap();
+        let (mut session, _) = Session::open(&dir.path().join("directory.json")).unwrap();
+        run_line("add Sally to Sales", &mut session).unwrap();
+        assert!(session.dirty);
+        assert!(!will_lose_changes(&session));
+    }
+}
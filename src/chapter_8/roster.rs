@@ -0,0 +1,180 @@
+// Importing and exporting the directory as a CSV roster.
+//
+// A roster has one row per membership: `name,department`, optionally
+// followed by `title`, `email`, `start-date` and `manager` columns. Somebody
+// in two departments takes two rows, and a row with an empty department adds
+// the person without putting them anywhere.
+//
+// If the first row is made of column names (it has at least `name` and
+// `department`), it is used to find the columns, in any order. Otherwise the
+// columns are expected in the order above, which is also the order `export`
+// writes them in.
+//
+// People, and their managers, are told apart by name alone, so a directory
+// where two people share a name can't be exported: importing it again would
+// make them one person.
+
+use std::collections::BTreeMap;
+
+use super::command::Command;
+use super::csv::{csv_line, parse_csv};
+use super::directory::{Directory, DirectoryError, Employee, Field};
+use super::names::name_key;
+
+const EXPORT_HEADER: [&str; 6] = ["name", "department", "title", "email", "start-date", "manager"];
+
+// What a roster turned into: the commands to run, each with the line it
+// came from, and the rows that couldn't be used at all, also by line.
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+    pub steps: Vec<(usize, Command)>,
+    pub errors: Vec<(usize, String)>,
+    pub warnings: Vec<String>,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RosterColumn {
+    Name,
+    Department,
+    Attribute(Field),
+    Ignored,
+}
+
+impl RosterColumn {
+    fn from_header(header: &str) -> Option<RosterColumn> {
+        match header.trim().to_lowercase().replace('_', "-").as_str() {
+            "name" => Some(RosterColumn::Name),
+            "department" => Some(RosterColumn::Department),
+            "title" => Some(RosterColumn::Attribute(Field::Title)),
+            "email" => Some(RosterColumn::Attribute(Field::Email)),
+            "start-date" => Some(RosterColumn::Attribute(Field::StartDate)),
+            "manager" => Some(RosterColumn::Attribute(Field::Manager)),
+            _ => None,
+        }
+    }
+}
+
+// Turns the text of a roster into `add` and `set` commands. Managers are set
+// after everybody has been added, so a row can name a manager who only shows
+// up further down the file.
+pub fn plan_import(text: &str) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+    let mut records = parse_csv(text).into_iter().peekable();
+
+    let mut columns: Vec<RosterColumn> = vec![
+        RosterColumn::Name,
+        RosterColumn::Department,
+        RosterColumn::Attribute(Field::Title),
+        RosterColumn::Attribute(Field::Email),
+        RosterColumn::Attribute(Field::StartDate),
+        RosterColumn::Attribute(Field::Manager),
+    ];
+    if let Some(first) = records.peek()
+        && let Ok(fields) = &first.fields
+    {
+        let header: Vec<Option<RosterColumn>> = fields.iter().map(|field| RosterColumn::from_header(field)).collect();
+        if header.contains(&Some(RosterColumn::Name)) && header.contains(&Some(RosterColumn::Department)) {
+            for (field, column) in fields.iter().zip(&header) {
+                if column.is_none() {
+                    plan.warnings.push(format!("line {}: ignoring the unknown column `{}`", first.line, field));
+                }
+            }
+            columns = header.into_iter().map(|column| column.unwrap_or(RosterColumn::Ignored)).collect();
+            records.next();
+        }
+    }
+
+    let mut manager_steps = Vec::new();
+    for record in records {
+        plan.rows += 1;
+        let fields = match record.fields {
+            Ok(fields) => fields,
+            Err(error) => {
+                plan.errors.push((record.line, error));
+                continue;
+            }
+        };
+        if fields.len() > columns.len() {
+            plan.warnings.push(format!(
+                "line {}: ignoring {} extra fields",
+                record.line,
+                fields.len() - columns.len()
+            ));
+        }
+
+        let mut name = None;
+        let mut department = None;
+        let mut attributes = Vec::new();
+        for (value, column) in fields.iter().map(|value| value.trim()).zip(&columns) {
+            match column {
+                RosterColumn::Name => name = Some(value),
+                RosterColumn::Department => department = Some(value),
+                RosterColumn::Attribute(field) if !value.is_empty() => attributes.push((*field, value)),
+                RosterColumn::Attribute(_) | RosterColumn::Ignored => {}
+            }
+        }
+        let Some(name) = name.filter(|name| !name.is_empty()) else {
+            plan.errors.push((record.line, "the name is missing".to_string()));
+            continue;
+        };
+        let Some(department) = department else {
+            plan.errors.push((record.line, "the department is missing".to_string()));
+            continue;
+        };
+
+        let department = Some(department.to_string()).filter(|department| !department.is_empty());
+        plan.steps.push((record.line, Command::Add { name: name.to_string(), department }));
+        for (field, value) in attributes {
+            let step = Command::Set { employee: name.to_string(), field, value: value.to_string() };
+            if field == Field::Manager {
+                manager_steps.push((record.line, step));
+            } else {
+                plan.steps.push((record.line, step));
+            }
+        }
+    }
+    plan.steps.extend(manager_steps);
+    plan
+}
+
+// The whole directory as roster text, one row per membership, sorted by name.
+pub fn export_roster(directory: &Directory) -> Result<(String, usize), DirectoryError> {
+    let mut by_name: BTreeMap<String, Vec<&Employee>> = BTreeMap::new();
+    for employee in directory.employees() {
+        by_name.entry(name_key(&employee.name)).or_default().push(employee);
+    }
+    if let Some(shared) = by_name.values().find(|employees| employees.len() > 1) {
+        let ids = shared.iter().map(|employee| employee.id).collect();
+        return Err(DirectoryError::SharedName { name: shared[0].name.clone(), ids });
+    }
+
+    let header: Vec<String> = EXPORT_HEADER.iter().map(|column| column.to_string()).collect();
+    let mut text = csv_line(&header);
+    text.push('\n');
+
+    let mut employees: Vec<_> = directory.employees().collect();
+    employees.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    let mut rows = 0;
+    for employee in employees {
+        let manager = employee.manager.map(|manager| directory.name_of(manager));
+        let mut departments = directory.departments_of(employee.id);
+        if departments.is_empty() {
+            departments.push("");
+        }
+        for department in departments {
+            let row = [
+                employee.name.clone(),
+                department.to_string(),
+                employee.title.clone().unwrap_or_default(),
+                employee.email.clone().unwrap_or_default(),
+                employee.start_date.clone().unwrap_or_default(),
+                manager.clone().unwrap_or_default(),
+            ];
+            text.push_str(&csv_line(&row));
+            text.push('\n');
+            rows += 1;
+        }
+    }
+    Ok((text, rows))
+}
@@ -0,0 +1,62 @@
+// Running department commands from a file instead of the prompt.
+//
+// A script is the same commands the prompt takes, one per line:
+//
+// # New starters for March
+// add "Mary Ann" to Sales
+// set "Mary Ann" start-date 2024-03-04
+//
+// Blank lines and lines starting with `#` are skipped. Output is printed as
+// it would be at the prompt, just without the prompt itself. Errors go to
+// stderr with the line they came from (`roster.cmds:3: there is no Sales
+// department`) and the script carries on with the next line, unless it was
+// asked to stop at the first one. `quit` ends the script early.
+//
+// With a structured output format (see `output`) errors are records like
+// everything else and go to stdout with the rest, so one stream has it all.
+
+use std::io::{self, BufRead};
+
+use super::common_collections::{run_line, Outcome};
+use super::output::{render_error, OutputFormat};
+use super::session::Session;
+
+// How a script run went, for working out the exit code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptSummary {
+    pub commands: usize,
+    pub failed: usize,
+}
+
+// Runs every line of `script` against the session. `name` is only used to
+// label errors. Fails only if the script itself can't be read.
+pub fn run_script(name: &str, script: impl BufRead, stop_on_error: bool, session: &mut Session) -> io::Result<ScriptSummary> {
+    let mut summary = ScriptSummary::default();
+    for (index, line) in script.lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        summary.commands += 1;
+        match run_line(&line, session) {
+            Ok((Outcome::Quit, _)) => break,
+            Ok((_, output)) => {
+                for line in output.render(session.format) {
+                    println!("{}", line);
+                }
+            }
+            Err(error) => {
+                match session.format {
+                    OutputFormat::Text => eprintln!("{}:{}: {}", name, index + 1, error),
+                    format => println!("{}", render_error(&error, Some(index + 1), format)),
+                }
+                summary.failed += 1;
+                if stop_on_error {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(summary)
+}
@@ -0,0 +1,179 @@
+// Finding people in the directory by (part of) their name.
+//
+// Matching ignores case and extra spaces, the same way names are looked up
+// everywhere else (see `names::name_key`). A query without wildcards matches names that are
+// equal to it, start with it, have a word starting with it, or contain it
+// anywhere, and results are ranked in that order. A query with `*` (any
+// run of characters) or `?` (exactly one character) has to match the whole
+// name instead, so `j*n` finds Jon and Jillian but not Jonas.
+
+use super::directory::{Directory, Employee};
+use super::names::name_key;
+
+// How well a name matched, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    WordPrefix,
+    Substring,
+    Wildcard,
+}
+
+impl MatchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchKind::Exact => "exact match",
+            MatchKind::Prefix => "starts with",
+            MatchKind::WordPrefix => "word starts with",
+            MatchKind::Substring => "contains",
+            MatchKind::Wildcard => "wildcard match",
+        }
+    }
+}
+
+pub fn match_name(query: &str, name: &str) -> Option<MatchKind> {
+    let query = name_key(query);
+    let name = name_key(name);
+    if query.is_empty() {
+        return None;
+    }
+
+    if query.contains(['*', '?']) {
+        let query: Vec<char> = query.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        return wildcard_match(&query, &name).then_some(MatchKind::Wildcard);
+    }
+
+    if name == query {
+        Some(MatchKind::Exact)
+    } else if name.starts_with(&query) {
+        Some(MatchKind::Prefix)
+    } else if name.split_whitespace().any(|word| word.starts_with(&query)) {
+        Some(MatchKind::WordPrefix)
+    } else if name.contains(&query) {
+        Some(MatchKind::Substring)
+    } else {
+        None
+    }
+}
+
+// Everybody whose name matches `query`, best matches first. Ties go to the
+// shorter name, since it is closer to what was typed, then alphabetically.
+pub fn find<'a>(directory: &'a Directory, query: &str) -> Vec<(MatchKind, &'a Employee)> {
+    let mut hits: Vec<(MatchKind, &Employee)> = directory
+        .employees()
+        .filter_map(|employee| match_name(query, &employee.name).map(|kind| (kind, employee)))
+        .collect();
+    hits.sort_by(|(a_kind, a), (b_kind, b)| {
+        a_kind
+            .cmp(b_kind)
+            .then(a.name.chars().count().cmp(&b.name.chars().count()))
+            .then(a.name.cmp(&b.name))
+            .then(a.id.cmp(&b.id))
+    });
+    hits
+}
+
+// Classic glob matching, keeping track of the last `*` so it can be made to
+// swallow one more character when the rest doesn't line up.
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            last_star = Some((p, t));
+            p += 1;
+        } else if let Some((star, star_text)) = last_star {
+            p = star + 1;
+            t = star_text + 1;
+            last_star = Some((star, star_text + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(names: &[&str]) -> Directory {
+        let mut directory = Directory::new();
+        for name in names {
+            directory.resolve_or_create(name).unwrap();
+        }
+        directory
+    }
+
+    fn found(directory: &Directory, query: &str) -> Vec<(MatchKind, String)> {
+        find(directory, query).into_iter().map(|(kind, employee)| (kind, employee.name.clone())).collect()
+    }
+
+    #[test]
+    fn star_matches_any_run_of_characters_including_none() {
+        assert_eq!(match_name("j*n", "Jon"), Some(MatchKind::Wildcard));
+        assert_eq!(match_name("j*n", "Jillian"), Some(MatchKind::Wildcard));
+        assert_eq!(match_name("jo*n", "Jon"), Some(MatchKind::Wildcard));
+        assert_eq!(match_name("*", "Anyone"), Some(MatchKind::Wildcard));
+        assert_eq!(match_name("**n", "Jon"), Some(MatchKind::Wildcard));
+    }
+
+    #[test]
+    fn question_mark_matches_exactly_one_character() {
+        assert_eq!(match_name("j?n", "Jon"), Some(MatchKind::Wildcard));
+        assert_eq!(match_name("j?n", "Jn"), None);
+        assert_eq!(match_name("j?n", "Joan"), None);
+    }
+
+    #[test]
+    fn wildcards_have_to_match_the_whole_name() {
+        assert_eq!(match_name("j*n", "Jonas"), None);
+        assert_eq!(match_name("*n", "Jonas"), None);
+        assert_eq!(match_name("o*", "Jon"), None);
+        assert_eq!(match_name("*o*", "Jon"), Some(MatchKind::Wildcard));
+    }
+
+    #[test]
+    fn case_and_spaces_do_not_matter() {
+        assert_eq!(match_name("MARY  ann", "Mary Ann"), Some(MatchKind::Exact));
+        assert_eq!(match_name("M*ANN", "mary ann"), Some(MatchKind::Wildcard));
+        assert_eq!(match_name("STRASSE", "Straße"), Some(MatchKind::Exact));
+    }
+
+    #[test]
+    fn plain_queries_say_how_they_matched() {
+        assert_eq!(match_name("ann", "Ann"), Some(MatchKind::Exact));
+        assert_eq!(match_name("ann", "Annabel"), Some(MatchKind::Prefix));
+        assert_eq!(match_name("ann", "Mary Ann"), Some(MatchKind::WordPrefix));
+        assert_eq!(match_name("ann", "Joanna"), Some(MatchKind::Substring));
+        assert_eq!(match_name("ann", "Bo"), None);
+        assert_eq!(match_name("  ", "Bo"), None);
+    }
+
+    #[test]
+    fn better_matches_come_first_then_shorter_names() {
+        let directory = directory(&["Joanna", "Mary Ann", "Annabel", "Ann", "Anne", "Bo"]);
+        assert_eq!(
+            found(&directory, "ann"),
+            [
+                (MatchKind::Exact, "Ann".to_string()),
+                (MatchKind::Prefix, "Anne".to_string()),
+                (MatchKind::Prefix, "Annabel".to_string()),
+                (MatchKind::WordPrefix, "Mary Ann".to_string()),
+                (MatchKind::Substring, "Joanna".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_of_the_same_length_are_alphabetical() {
+        let directory = directory(&["Jon", "Jan", "Jillian", "Jonas"]);
+        let names: Vec<String> = found(&directory, "j*n").into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, ["Jan", "Jon", "Jillian"]);
+    }
+}
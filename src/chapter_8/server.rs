@@ -0,0 +1,326 @@
+// The directory over HTTP, for other tools on the same machine.
+//
+// `untitled directory --serve <port>` listens on 127.0.0.1 only and answers JSON:
+//
+// GET    /departments                          every department, like `org-chart`
+// GET    /departments/<name>[?recursive=true]  the people in one, like `list`
+// GET    /employees                            everybody, like `list-all`
+// GET    /search?q=<query>                     like `find`
+// POST   /employees                            {"name": "Amir", "department": "Sales"},
+//                                              like `add`, the department is optional
+// DELETE /departments/<name>/members/<person>  like `remove`
+//
+// Each request is turned into the same `Command` the prompt would parse and
+// run through `run_command`, so permissions, the journal and the audit log
+// all work as they do at the prompt. Bodies are what `--format json` prints:
+// `{"records":[...]}` or `{"messages":[...]}`, and errors are
+// `{"error":{"code":...,"message":...}}` with a 4xx or 5xx status. With a
+// users file (see `access`) the `X-User` header says who is asking,
+// without it nobody is logged in.
+//
+// Requests are handled one at a time, in the order they come in.
+
+use std::io;
+
+use serde::Deserialize;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::access::AccessError;
+use super::command::Command;
+use super::common_collections::{run_command, CommandError};
+use super::directory::DirectoryError;
+use super::output::{render_error, OutputFormat};
+use super::session::Session;
+
+pub struct ApiServer {
+    server: Server,
+}
+
+#[derive(Deserialize)]
+struct NewEmployee {
+    name: String,
+    #[serde(default)]
+    department: Option<String>,
+}
+
+impl ApiServer {
+    // Port 0 picks any free port, see `port` for which.
+    pub fn bind(port: u16) -> io::Result<ApiServer> {
+        let server = Server::http(("127.0.0.1", port)).map_err(io::Error::other)?;
+        Ok(ApiServer { server })
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.server.server_addr().to_ip().map(|address| address.port())
+    }
+
+    // Answers requests until the process is stopped.
+    pub fn run(&self, session: &mut Session) {
+        for request in self.server.incoming_requests() {
+            respond(request, session);
+        }
+    }
+}
+
+fn respond(mut request: Request, session: &mut Session) {
+    let mut body = String::new();
+    let (status, text) = match request.as_reader().read_to_string(&mut body) {
+        Ok(_) => {
+            let user = request
+                .headers()
+                .iter()
+                .find(|header| header.field.equiv("X-User"))
+                .map(|header| header.value.as_str().to_string());
+            handle_request(request.method(), request.url(), &body, user.as_deref(), session)
+        }
+        Err(_) => error_response(400, "bad_request", "the body is not UTF-8 text"),
+    };
+    let content_type = Header::from_bytes("Content-Type", "application/json").expect("a valid header");
+    let response = Response::from_string(text).with_status_code(status).with_header(content_type);
+    // Nothing to do if the client has already gone.
+    let _ = request.respond(response);
+}
+
+// Works out the command a request stands for, runs it and returns the
+// status and body to answer with.
+pub fn handle_request(method: &Method, url: &str, body: &str, user: Option<&str>, session: &mut Session) -> (u16, String) {
+    match user {
+        Some(user) => match session.access.login(user) {
+            // Without a users file everybody may do everything anyway.
+            Ok(_) | Err(AccessError::NoUsers) => {}
+            Err(error) => return command_error(&CommandError::Access(error)),
+        },
+        None => session.access.logout(),
+    }
+
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let Some(segments) = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode(segment, false))
+        .collect::<Option<Vec<String>>>()
+    else {
+        return error_response(400, "bad_request", "the path is not valid percent-encoded UTF-8");
+    };
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let parameter = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .and_then(|(_, value)| percent_decode(value, true))
+    };
+
+    let command = match (method, segments.as_slice()) {
+        (Method::Get, ["departments"]) => Command::OrgChart,
+        (Method::Get, ["departments", department]) => Command::List {
+            department: department.to_string(),
+            recursive: parameter("recursive").is_some_and(|value| value == "true"),
+            columns: Vec::new(),
+        },
+        (Method::Get, ["employees"]) => Command::ListAll { columns: Vec::new() },
+        (Method::Get, ["search"]) => match parameter("q") {
+            Some(query) if !query.trim().is_empty() => Command::Find { query },
+            _ => return error_response(400, "bad_request", "search needs a q parameter"),
+        },
+        (Method::Post, ["employees"]) => match serde_json::from_str::<NewEmployee>(body) {
+            Ok(employee) => Command::Add { name: employee.name, department: employee.department },
+            Err(error) => return error_response(400, "bad_request", &format!("expected {{\"name\": ..., \"department\": ...}}: {}", error)),
+        },
+        (Method::Delete, ["departments", department, "members", employee]) => Command::Remove {
+            name: employee.to_string(),
+            department: department.to_string(),
+        },
+        (_, ["departments"] | ["departments", _] | ["employees"] | ["search"] | ["departments", _, "members", _]) => {
+            return error_response(405, "method_not_allowed", &format!("{} is not supported on {}", method, path));
+        }
+        _ => return error_response(404, "not_found", &format!("there is nothing at {}", path)),
+    };
+
+    match run_command(command, session) {
+        Ok((_, output)) => {
+            let body = output.render(OutputFormat::Json).join("\n");
+            // Output with nothing to say still answers with an object.
+            let body = if body.is_empty() { json!({ "messages": [] }).to_string() } else { body };
+            (200, body)
+        }
+        Err(error) => command_error(&error),
+    }
+}
+
+fn command_error(error: &CommandError) -> (u16, String) {
+    let status = match error {
+        CommandError::Parse(_) => 400,
+        CommandError::Access(_) => 403,
+        CommandError::Directory(error) => match error {
+            DirectoryError::NoSuchDepartment(_) | DirectoryError::NoSuchEmployee(_) | DirectoryError::NotInDepartment { .. } => 404,
+            DirectoryError::AmbiguousName { .. }
+            | DirectoryError::SharedName { .. }
+            | DirectoryError::AlreadyInDepartment { .. }
+            | DirectoryError::AlreadyInDirectory(_)
+            | DirectoryError::DepartmentExists(_) => 409,
+            DirectoryError::Storage(_) | DirectoryError::File { .. } => 500,
+            _ => 422,
+        },
+    };
+    (status, render_error(error, None, OutputFormat::Json))
+}
+
+// For problems with the request itself, which have no `CommandError`.
+fn error_response(status: u16, code: &str, message: &str) -> (u16, String) {
+    (status, json!({ "error": { "code": code, "message": message } }).to_string())
+}
+
+// Undoes %XX escapes, and in query strings `+` for a space. None if the
+// result isn't UTF-8 or an escape is broken.
+fn percent_decode(text: &str, is_query: bool) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+                continue;
+            }
+            b'+' if is_query => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+        rest = tail;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::path::Path;
+    use std::thread;
+
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    use crate::chapter_8::access::Access;
+    use crate::chapter_8::directory::Directory;
+
+    // Starts a server on a free port in the background. It lives until the
+    // test process ends.
+    fn start(mut session: Session) -> u16 {
+        let server = ApiServer::bind(0).unwrap();
+        let port = server.port().unwrap();
+        thread::spawn(move || server.run(&mut session));
+        port
+    }
+
+    // A minimal HTTP client, returns the status and the parsed body.
+    fn request(port: u16, method: &str, path: &str, user: Option<&str>, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let user = user.map(|user| format!("X-User: {}\r\n", user)).unwrap_or_default();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            user,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn names(records: &Value) -> Vec<&str> {
+        records["records"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|record| record["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn adds_lists_searches_and_removes() {
+        let port = start(Session::new(Directory::new()));
+
+        let (status, body) = request(port, "POST", "/employees", None, r#"{"name": "Mary Ann", "department": "Sales"}"#);
+        assert_eq!(status, 200);
+        assert!(body["messages"].as_array().is_some_and(|messages| !messages.is_empty()));
+        request(port, "POST", "/employees", None, r#"{"name": "Amir"}"#);
+
+        let (status, body) = request(port, "GET", "/departments/sales", None, "");
+        assert_eq!(status, 200);
+        assert_eq!(names(&body), ["Mary Ann"]);
+        let (_, body) = request(port, "GET", "/employees", None, "");
+        assert_eq!(names(&body), ["Amir", "Mary Ann"]);
+        let (_, body) = request(port, "GET", "/search?q=mary+a*", None, "");
+        assert_eq!(names(&body), ["Mary Ann"]);
+        let (_, body) = request(port, "GET", "/departments", None, "");
+        assert_eq!(names(&body), ["Sales"]);
+
+        let (status, _) = request(port, "DELETE", "/departments/Sales/members/Mary%20Ann", None, "");
+        assert_eq!(status, 200);
+        let (_, body) = request(port, "GET", "/departments/Sales", None, "");
+        assert!(names(&body).is_empty());
+    }
+
+    #[test]
+    fn errors_come_back_as_json_with_a_status() {
+        let port = start(Session::new(Directory::new()));
+
+        let (status, body) = request(port, "DELETE", "/departments/Sales/members/Amir", None, "");
+        assert_eq!(status, 404);
+        assert_eq!(body["error"]["code"], "no_such_employee");
+        let (status, body) = request(port, "POST", "/employees", None, "not json");
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["code"], "bad_request");
+        let (status, _) = request(port, "GET", "/nowhere", None, "");
+        assert_eq!(status, 404);
+        let (status, _) = request(port, "PUT", "/employees", None, "");
+        assert_eq!(status, 405);
+    }
+
+    #[test]
+    fn the_user_header_decides_what_is_allowed() {
+        let dir = TempDir::new().unwrap();
+        let users = dir.path().join("users.json");
+        fs::write(&users, r#"{"users": {"Sally": "editor", "Bo": "viewer"}}"#).unwrap();
+        let mut session = Session::new(Directory::new());
+        session.access = Access::open(&users, &dir.path().join("denials.log")).unwrap();
+        let port = start(session);
+
+        let new_employee = r#"{"name": "Amir", "department": "Sales"}"#;
+        let (status, body) = request(port, "POST", "/employees", None, new_employee);
+        assert_eq!(status, 403);
+        assert_eq!(body["error"]["code"], "permission_denied");
+        let (status, _) = request(port, "POST", "/employees", Some("Bo"), new_employee);
+        assert_eq!(status, 403);
+        let (status, _) = request(port, "POST", "/employees", Some("Nobody"), new_employee);
+        assert_eq!(status, 403);
+        let (status, _) = request(port, "POST", "/employees", Some("sally"), new_employee);
+        assert_eq!(status, 200);
+        // Reading is fine for anybody.
+        let (status, body) = request(port, "GET", "/employees", None, "");
+        assert_eq!(status, 200);
+        assert_eq!(names(&body), ["Amir"]);
+        assert!(Path::new(&dir.path().join("denials.log")).exists());
+    }
+
+    #[test]
+    fn percent_escapes_are_decoded() {
+        assert_eq!(percent_decode("Mary%20Ann", false).as_deref(), Some("Mary Ann"));
+        assert_eq!(percent_decode("a+b", false).as_deref(), Some("a+b"));
+        assert_eq!(percent_decode("a+b", true).as_deref(), Some("a b"));
+        assert_eq!(percent_decode("Stra%C3%9Fe", false).as_deref(), Some("Straße"));
+        assert_eq!(percent_decode("50%", false), None);
+        assert_eq!(percent_decode("%FF", false), None);
+    }
+}
@@ -0,0 +1,101 @@
+// Everything the department prompt works on between commands: the
+// directory itself, its undo history and, when it is kept on disk, the
+// storage it is saved to.
+
+use std::io;
+use std::path::Path;
+
+use super::access::Access;
+use super::audit::AuditLog;
+use super::command::parse_command;
+use super::common_collections::{execute, replay_steps};
+use super::directory::Directory;
+use super::history::History;
+use super::output::OutputFormat;
+use super::persistence::Storage;
+
+#[derive(Debug, Default)]
+pub struct Session {
+    pub directory: Directory,
+    pub history: History,
+    // None for a directory that only lives in memory.
+    pub storage: Option<Storage>,
+    // How command results are printed.
+    pub format: OutputFormat,
+    // Who is at the prompt and what they may change.
+    pub access: Access,
+    // Where changes are audited, if anywhere.
+    pub audit: Option<AuditLog>,
+    // Whether the directory has changes that aren't in a snapshot yet. With
+    // storage they are in the journal until it is compacted, without they
+    // are only in memory and lost on leaving.
+    pub dirty: bool,
+}
+
+// What happened while opening a saved directory, for the caller to report.
+#[derive(Debug, Default)]
+pub struct Recovery {
+    // Journal records replayed on top of the snapshot.
+    pub replayed: usize,
+    // Whether a half written record at the end of the journal was dropped.
+    pub truncated: bool,
+    // Problems in the snapshot that were fixed up while loading it.
+    pub warnings: Vec<String>,
+}
+
+impl Session {
+    // A session that is not saved anywhere.
+    pub fn new(directory: Directory) -> Self {
+        Session {
+            directory,
+            history: History::new(),
+            storage: None,
+            format: OutputFormat::Text,
+            access: Access::default(),
+            audit: None,
+            dirty: false,
+        }
+    }
+
+    // Loads the directory saved at `path` and replays its journal, so the
+    // session picks up exactly where the last one stopped, crash or not.
+    // The replayed commands also go back into the history, so they can
+    // still be undone.
+    pub fn open(path: &Path) -> io::Result<(Session, Recovery)> {
+        let opened = Storage::open(path)?;
+        let mut session = Session::new(opened.directory);
+        for record in &opened.records {
+            if !record.steps.is_empty() {
+                replay_steps(&record.command, &record.steps, &mut session);
+                continue;
+            }
+            // The journal holds commands exactly as they were run, including
+            // ones that failed, which fail the same way again here.
+            if let Ok(command) = parse_command(&record.command) {
+                let _ = execute(command, &mut session);
+            }
+        }
+        // Attached only now so the replay isn't written to the journal again.
+        session.storage = Some(opened.storage);
+        session.dirty = !opened.records.is_empty();
+        let recovery = Recovery {
+            replayed: opened.records.len(),
+            truncated: opened.truncated,
+            warnings: opened.warnings,
+        };
+        Ok((session, recovery))
+    }
+
+    // Folds the journal into a new snapshot and returns how many records it
+    // held. The history is cleared too: a replay after this starts from the
+    // new snapshot, where those operations can't be undone anymore.
+    pub fn compact(&mut self) -> io::Result<usize> {
+        let Some(storage) = &mut self.storage else {
+            return Ok(0);
+        };
+        let folded = storage.compact(&self.directory)?;
+        self.history = History::new();
+        self.dirty = false;
+        Ok(folded)
+    }
+}
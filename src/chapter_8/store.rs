@@ -0,0 +1,235 @@
+// Where the directory lives, as far as the command handlers care.
+//
+// `DirectoryStore` is the handful of reads and changes the commands need.
+// Handlers written against it don't care what is behind it. Only `add` and
+// `stats` are written that way so far, the other handlers and undo still
+// work on a `Directory`, and so does `Session`.
+//
+// The only backend is `Directory` itself. Saving it is left to the session,
+// which journals every change and folds the journal into the snapshot (see
+// `persistence`). A backend that writes the snapshot itself would have to
+// take over the journal too, or a session opening the same file would
+// replay old journal records on top of newer data.
+//
+// Everything is handed out by value so a backend doesn't have to keep the
+// whole directory in memory to implement it. Every backend has to pass the
+// conformance tests at the bottom of this file.
+
+use super::directory::{Department, Directory, DirectoryError, Employee, EmployeeId};
+use super::names::name_key;
+
+pub trait DirectoryStore {
+    fn employee(&self, id: EmployeeId) -> Option<Employee>;
+
+    // Sorted by id.
+    fn employees(&self) -> Vec<Employee>;
+
+    // Looked up by `name_key`, like everything that takes a name.
+    fn department(&self, name: &str) -> Option<Department>;
+
+    // Sorted by name, ignoring case.
+    fn departments(&self) -> Vec<Department>;
+
+    // Finds the employee `reference` points at (`#3`, `3` or the name of
+    // exactly one person, see `Directory::resolve`), and makes a new name
+    // into a new employee. Returns the id and whether the employee was just
+    // created.
+    fn resolve_or_create(&mut self, reference: &str) -> Result<(EmployeeId, bool), DirectoryError>;
+
+    // Adds `id` to `department`, creating the department at the top level if
+    // it doesn't exist. Returns true when the department was created.
+    fn add_member(&mut self, department: &str, id: EmployeeId) -> Result<bool, DirectoryError>;
+
+    // The name of `id`, or the id itself if they are not in the directory.
+    fn name_of(&self, id: EmployeeId) -> String {
+        match self.employee(id) {
+            Some(employee) => employee.name,
+            None => format!("#{}", id),
+        }
+    }
+
+    // The names of every department `id` belongs to, sorted like `departments`.
+    fn departments_of(&self, id: EmployeeId) -> Vec<String> {
+        self.departments()
+            .into_iter()
+            .filter(|department| department.members.contains(&id))
+            .map(|department| department.name)
+            .collect()
+    }
+
+    // The department `department` sits in, None at the top level.
+    fn parent(&self, department: &Department) -> Option<Department> {
+        let parent = department.parent.as_ref()?;
+        self.departments()
+            .into_iter()
+            .find(|candidate| name_key(&candidate.name) == *parent)
+    }
+
+    // The departments directly inside `department`, or the top level ones
+    // for None, sorted like `departments`.
+    fn subdepartments(&self, department: Option<&Department>) -> Vec<Department> {
+        let parent = department.map(|department| name_key(&department.name));
+        self.departments()
+            .into_iter()
+            .filter(|child| child.parent == parent)
+            .collect()
+    }
+
+    // `department` itself followed by everything inside it, at any depth.
+    fn departments_below(&self, department: &Department) -> Vec<Department> {
+        let mut below = vec![department.clone()];
+        for child in self.subdepartments(Some(department)) {
+            below.extend(self.departments_below(&child));
+        }
+        below
+    }
+
+    // Everybody in `department` or any department inside it, each once.
+    fn members_below(&self, department: &Department) -> Vec<EmployeeId> {
+        let mut members = Vec::new();
+        for below in self.departments_below(department) {
+            for id in below.members {
+                if !members.contains(&id) {
+                    members.push(id);
+                }
+            }
+        }
+        members
+    }
+}
+
+impl DirectoryStore for Directory {
+    fn employee(&self, id: EmployeeId) -> Option<Employee> {
+        Directory::employee(self, id).cloned()
+    }
+
+    fn employees(&self) -> Vec<Employee> {
+        Directory::employees(self).cloned().collect()
+    }
+
+    fn department(&self, name: &str) -> Option<Department> {
+        Directory::department(self, name).cloned()
+    }
+
+    fn departments(&self) -> Vec<Department> {
+        Directory::departments(self).cloned().collect()
+    }
+
+    fn resolve_or_create(&mut self, reference: &str) -> Result<(EmployeeId, bool), DirectoryError> {
+        Directory::resolve_or_create(self, reference)
+    }
+
+    fn add_member(&mut self, department: &str, id: EmployeeId) -> Result<bool, DirectoryError> {
+        Directory::add_member(self, department, id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The behaviour every backend has to share. Each check gets a fresh,
+    // empty store.
+
+    fn new_store_is_empty(store: &mut impl DirectoryStore) {
+        assert!(store.employees().is_empty());
+        assert!(store.departments().is_empty());
+        assert_eq!(store.department("Sales"), None);
+    }
+
+    fn new_names_become_employees_with_increasing_ids(store: &mut impl DirectoryStore) {
+        assert_eq!(store.resolve_or_create("Sally"), Ok((1, true)));
+        assert_eq!(store.resolve_or_create("Amir"), Ok((2, true)));
+        assert_eq!(store.resolve_or_create("Sally"), Ok((1, false)));
+        let names: Vec<String> = store.employees().into_iter().map(|employee| employee.name).collect();
+        assert_eq!(names, ["Sally", "Amir"]);
+    }
+
+    fn references_resolve_by_id_and_by_name(store: &mut impl DirectoryStore) {
+        let (id, _) = store.resolve_or_create("Mary Ann").unwrap();
+        assert_eq!(store.resolve_or_create("#1"), Ok((id, false)));
+        assert_eq!(store.resolve_or_create("1"), Ok((id, false)));
+        assert_eq!(store.resolve_or_create("  mary   ANN "), Ok((id, false)));
+        assert_eq!(
+            store.resolve_or_create("#9"),
+            Err(DirectoryError::NoSuchEmployee("#9".to_string()))
+        );
+        assert_eq!(store.employees().len(), 1);
+    }
+
+    fn adding_creates_the_department_once(store: &mut impl DirectoryStore) {
+        let (sally, _) = store.resolve_or_create("Sally").unwrap();
+        let (amir, _) = store.resolve_or_create("Amir").unwrap();
+        assert_eq!(store.add_member("Engineering", sally), Ok(true));
+        assert_eq!(store.add_member("engineering", amir), Ok(false));
+
+        let department = store.department("ENGINEERING").unwrap();
+        assert_eq!(department.name, "Engineering");
+        assert_eq!(department.members, [sally, amir]);
+        assert_eq!(department.parent, None);
+    }
+
+    fn adding_twice_is_an_error(store: &mut impl DirectoryStore) {
+        let (sally, _) = store.resolve_or_create("Sally").unwrap();
+        store.add_member("Sales", sally).unwrap();
+        assert_eq!(
+            store.add_member("sales", sally),
+            Err(DirectoryError::AlreadyInDepartment {
+                name: "Sally".to_string(),
+                department: "Sales".to_string(),
+            })
+        );
+        assert_eq!(store.department("Sales").unwrap().members, [sally]);
+    }
+
+    fn departments_come_back_sorted_ignoring_case(store: &mut impl DirectoryStore) {
+        let (sally, _) = store.resolve_or_create("Sally").unwrap();
+        for department in ["sales", "Engineering", "Accounts"] {
+            store.add_member(department, sally).unwrap();
+        }
+        let names: Vec<String> = store.departments().into_iter().map(|department| department.name).collect();
+        assert_eq!(names, ["Accounts", "Engineering", "sales"]);
+        assert_eq!(store.departments_of(sally), ["Accounts", "Engineering", "sales"]);
+        assert_eq!(store.subdepartments(None).len(), 3);
+    }
+
+    fn name_of_falls_back_to_the_id(store: &mut impl DirectoryStore) {
+        let (sally, _) = store.resolve_or_create("Sally").unwrap();
+        assert_eq!(store.name_of(sally), "Sally");
+        assert_eq!(store.name_of(42), "#42");
+        assert_eq!(store.employee(42), None);
+    }
+
+    macro_rules! conformance_tests {
+        ($backend:ident, $new_store:expr) => {
+            mod $backend {
+                use super::*;
+
+                conformance_tests!(@each $new_store,
+                    new_store_is_empty,
+                    new_names_become_employees_with_increasing_ids,
+                    references_resolve_by_id_and_by_name,
+                    adding_creates_the_department_once,
+                    adding_twice_is_an_error,
+                    departments_come_back_sorted_ignoring_case,
+                    name_of_falls_back_to_the_id,
+                );
+            }
+        };
+        (@each $new_store:expr, $($check:ident),* $(,)?) => {
+            $(
+                #[test]
+                fn $check() {
+                    let (mut store, _dir) = $new_store;
+                    super::$check(&mut store);
+                }
+            )*
+        };
+    }
+
+    fn new_memory_store() -> (Directory, ()) {
+        (Directory::new(), ())
+    }
+
+    conformance_tests!(memory, new_memory_store());
+}
@@ -0,0 +1,352 @@
+// The command line of the `untitled` programs.
+//
+// `untitled` has a subcommand for every program in here, so each can be run
+// (and scripted) on its own. The chapter demos are also binaries of their
+// own (`src/bin`), which take the same arguments as their subcommand.
+// Bad arguments are reported by clap with the usage and exit code 2, every
+// other failure by `report`, with the exit code for its kind. Settings
+// that aren't given as flags come from the environment or the config file,
+// see `config`.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::chapter_2::guessing_game;
+use crate::chapter_3::fibonacci::fibonacci;
+use crate::chapter_3::temperature_converter::convert_temperature;
+use crate::chapter_8::access::{Access, DEFAULT_DENIAL_LOG_PATH, DEFAULT_USERS_PATH};
+use crate::chapter_8::audit::{AuditLog, DEFAULT_AUDIT_LOG_PATH};
+use crate::chapter_8::output::OutputFormat;
+use crate::chapter_8::persistence::DEFAULT_DIRECTORY_PATH;
+use crate::chapter_8::repl::{install_interrupt_handler, run_repl, DEFAULT_PROMPT};
+use crate::chapter_8::script::run_script;
+use crate::chapter_8::server::ApiServer;
+use crate::chapter_8::session::Session;
+use crate::chapter_8::{_find_mode, find_median, pig_latin_in_dialect, Dialect};
+use crate::config::{Config, DirectoryConfig, GuessConfig, PigLatinConfig};
+use crate::error::Error;
+
+const DEFAULT_GUESS_LOW: u32 = 1;
+const DEFAULT_GUESS_HIGH: u32 = 10;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "untitled",
+    version,
+    about = "Small programs from working through the Rust book",
+    after_help = "Settings come from flags first, then UNTITLED_* environment variables, then the config file."
+)]
+pub struct Cli {
+    /// Read the settings from <FILE> instead of ~/.config/untitled/config.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: CliCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Print the median and mode of a list of integers
+    Stats {
+        /// The integers, at least one
+        // Not `required`, so no numbers is reported like every other error, with its own exit code.
+        #[arg(allow_negative_numbers = true)]
+        numbers: Vec<i32>,
+    },
+    /// Convert a sentence to pig latin
+    Piglatin {
+        /// The words to convert. Without any, every line of stdin is converted
+        words: Vec<String>,
+        /// What words starting with a vowel end in: yay, way, hay or ay [default: yay]
+        #[arg(long, value_parser = parse_dialect)]
+        dialect: Option<Dialect>,
+    },
+    /// Work on the company directory, at a prompt unless told otherwise
+    Directory(DirectoryArgs),
+    /// Play the number guessing game
+    Guess(GuessArgs),
+    /// Print the nth Fibonacci number, counting 1, 1, 2, 3, ...
+    Fib(FibArgs),
+    /// Convert a temperature between Fahrenheit and Celsius
+    ConvertTemp(ConvertTempArgs),
+}
+
+#[derive(Debug, Args)]
+struct DirectoryArgs {
+    /// Run the department commands in <FILE> (- for stdin) and exit
+    #[arg(long, value_name = "FILE", conflicts_with = "serve")]
+    script: Option<String>,
+    /// Stop the script at the first command that fails
+    #[arg(long, requires = "script")]
+    stop_on_error: bool,
+    /// Answer HTTP JSON requests about the directory on 127.0.0.1:<PORT>
+    #[arg(long, value_name = "PORT")]
+    serve: Option<u16>,
+    /// Print listings and errors as text, json or tsv [default: text]
+    #[arg(long, value_parser = parse_format)]
+    format: Option<OutputFormat>,
+    /// Keep the directory in <FILE> [default: company_directory.json]
+    #[arg(long, value_name = "FILE")]
+    data_file: Option<PathBuf>,
+    /// What the prompt looks like, {user} is whoever is logged in
+    #[arg(long)]
+    prompt: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct GuessArgs {
+    /// The smallest number that can come up [default: 1]
+    #[arg(long)]
+    low: Option<u32>,
+    /// The largest number that can come up [default: 10]
+    #[arg(long)]
+    high: Option<u32>,
+}
+
+#[derive(Debug, Args)]
+pub struct FibArgs {
+    /// Which one, up to 93 (the 94th doesn't fit in 64 bits)
+    #[arg(value_parser = clap::value_parser!(u64).range(0..=93))]
+    n: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertTempArgs {
+    #[arg(allow_negative_numbers = true)]
+    temperature: f64,
+    /// The scale the temperature is in, it is converted to the other one
+    #[arg(long, value_enum)]
+    from: Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Scale {
+    #[value(alias = "f")]
+    Fahrenheit,
+    #[value(alias = "c")]
+    Celsius,
+}
+
+// Runs whatever the command line asked for and returns the exit code.
+pub fn run(cli: Cli) -> i32 {
+    report("untitled", run_command(cli))
+}
+
+fn run_command(cli: Cli) -> Result<(), Error> {
+    // Only the programs with settings read the config, see `config`.
+    let config = || Config::load(cli.config.as_deref());
+    match cli.command {
+        CliCommand::Stats { numbers } => run_stats(&numbers),
+        CliCommand::Piglatin { words, dialect } => {
+            let dialect = match dialect {
+                Some(dialect) => dialect,
+                None => config()?.section::<PigLatinConfig>()?.dialect.unwrap_or_default(),
+            };
+            run_piglatin(words, dialect)
+        }
+        CliCommand::Directory(arguments) => run_directory(arguments, &config()?.section()?),
+        CliCommand::Guess(arguments) => arguments.run(&config()?),
+        CliCommand::Fib(arguments) => arguments.run(),
+        CliCommand::ConvertTemp(arguments) => arguments.run(),
+    }
+}
+
+// Turns how a program went into its exit code, telling the user what went
+// wrong first if anything did. See `Error` for the codes.
+pub fn report(program: &str, result: Result<(), Error>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("{}: {}", program, error);
+            error.exit_code()
+        }
+    }
+}
+
+impl GuessArgs {
+    // The range comes from the flags, or else from `config`.
+    pub fn run(&self, config: &Config) -> Result<(), Error> {
+        let guess: GuessConfig = config.section()?;
+        let low = self.low.or(guess.low).unwrap_or(DEFAULT_GUESS_LOW);
+        let high = self.high.or(guess.high).unwrap_or(DEFAULT_GUESS_HIGH);
+        guessing_game::play(low, high)
+    }
+}
+
+impl FibArgs {
+    pub fn run(&self) -> Result<(), Error> {
+        println!("{}", fibonacci(self.n as usize)?);
+        Ok(())
+    }
+}
+
+impl ConvertTempArgs {
+    pub fn run(&self) -> Result<(), Error> {
+        let mut converted = self.temperature;
+        match self.from {
+            Scale::Fahrenheit => {
+                convert_temperature(&mut converted, true)?;
+                println!("{}°F is {:.1}°C", self.temperature, converted);
+            }
+            Scale::Celsius => {
+                convert_temperature(&mut converted, false)?;
+                println!("{}°C is {:.1}°F", self.temperature, converted);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_format(format: &str) -> Result<OutputFormat, String> {
+    OutputFormat::from_name(format).ok_or_else(|| format!("unknown format `{}`, expected text, json or tsv", format))
+}
+
+fn parse_dialect(dialect: &str) -> Result<Dialect, String> {
+    Dialect::from_name(dialect).ok_or_else(|| format!("unknown dialect `{}`, expected yay, way, hay or ay", dialect))
+}
+
+// Loads the saved directory and replays its journal. If it can't be read we
+// stop here instead of starting empty, otherwise the next save would
+// overwrite whatever is in there. The users and the logs are kept next to
+// the directory.
+fn open_session(directory_path: &Path) -> Result<Session, Error> {
+    let (session, recovery) = Session::open(directory_path).map_err(|error| Error::file("load", directory_path, error))?;
+    for warning in &recovery.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if recovery.truncated {
+        eprintln!("The last change before the program stopped was only half saved and has been dropped.");
+    }
+    if recovery.replayed > 0 {
+        eprintln!("Recovered {} changes from the journal.", recovery.replayed);
+    }
+    Ok(Session {
+        access: open_access(directory_path)?,
+        audit: Some(AuditLog::new(&directory_path.with_file_name(DEFAULT_AUDIT_LOG_PATH))),
+        ..session
+    })
+}
+
+// Loads the users allowed to change the directory. A users file that can't
+// be read stops the program, rather than letting everybody do everything.
+fn open_access(directory_path: &Path) -> Result<Access, Error> {
+    let users_path = directory_path.with_file_name(DEFAULT_USERS_PATH);
+    Access::open(&users_path, &directory_path.with_file_name(DEFAULT_DENIAL_LOG_PATH)).map_err(|error| Error::file("load", &users_path, error))
+}
+
+// Serves the HTTP API until the process is stopped. Every change is in the
+// journal as soon as it is made, so stopping it at any point is safe.
+fn serve(directory_path: &Path, port: u16) -> Result<(), Error> {
+    let mut session = open_session(directory_path)?;
+    let server = ApiServer::bind(port).map_err(|source| Error::Io {
+        action: format!("listen on port {}", port),
+        source,
+    })?;
+    // Port 0 gets whichever port was free.
+    let port = server.port().unwrap_or(port);
+    eprintln!("Serving the directory on http://127.0.0.1:{}", port);
+    server.run(&mut session);
+    Ok(())
+}
+
+// Runs a script of department commands. It fails if any command did.
+fn run_script_file(directory_path: &Path, script: &str, stop_on_error: bool, format: OutputFormat) -> Result<(), Error> {
+    let mut session = open_session(directory_path)?;
+    session.format = format;
+    let summary = if script == "-" {
+        run_script("stdin", io::stdin().lock(), stop_on_error, &mut session)
+    } else {
+        match File::open(script) {
+            Ok(file) => run_script(script, BufReader::new(file), stop_on_error, &mut session),
+            Err(error) => Err(error),
+        }
+    };
+    // Like when leaving the prompt, nobody's role is checked for this.
+    if let Err(error) = session.compact() {
+        eprintln!("Could not compact {}: {}", directory_path.display(), error);
+    }
+    match summary {
+        Ok(summary) if summary.failed == 0 => Ok(()),
+        Ok(summary) => Err(Error::ScriptFailed {
+            failed: summary.failed,
+            commands: summary.commands,
+        }),
+        Err(error) => Err(Error::file("read", Path::new(script), error)),
+    }
+}
+
+fn run_stats(numbers: &[i32]) -> Result<(), Error> {
+    println!("The median for this list is {}", find_median(numbers)?);
+    println!("The mode of this list is {}", _find_mode(numbers)?);
+    Ok(())
+}
+
+fn run_piglatin(words: Vec<String>, dialect: Dialect) -> Result<(), Error> {
+    if !words.is_empty() {
+        let mut words = words.iter().flat_map(|word| word.split_whitespace()).map(|x| x.to_string()).collect();
+        let pig_latin_words: &mut Vec<String> = pig_latin_in_dialect(&mut words, dialect)?;
+        println!("{}", pig_latin_words.join(" "));
+        return Ok(());
+    }
+
+    // One sentence per line, so a whole file can be piped through.
+    for line in io::stdin().lock().lines() {
+        let user_string = line.map_err(|source| Error::Io {
+            action: "read stdin".to_string(),
+            source,
+        })?;
+        let mut user_string: Vec<String> = user_string
+            .split_whitespace()
+            .map(|x| x.to_string())
+            .collect();
+        // Blank lines stay blank.
+        if user_string.is_empty() {
+            println!();
+            continue;
+        }
+
+        let pig_latin_words: &mut Vec<String> = pig_latin_in_dialect(&mut user_string, dialect)?;
+        println!("{}", pig_latin_words.join(" "));
+    }
+    Ok(())
+}
+
+fn run_directory(arguments: DirectoryArgs, directory: &DirectoryConfig) -> Result<(), Error> {
+    let directory_path = arguments
+        .data_file
+        .or_else(|| directory.data_file.clone())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DIRECTORY_PATH));
+    let directory_path = directory_path.as_path();
+    let format = arguments.format.or(directory.format).unwrap_or_default();
+    if let Some(script) = &arguments.script {
+        return run_script_file(directory_path, script, arguments.stop_on_error, format);
+    }
+    if let Some(port) = arguments.serve {
+        return serve(directory_path, port);
+    }
+
+    // Only people want a greeting, it would break json or tsv output.
+    if format == OutputFormat::Text {
+        println!("Welcome the department program...");
+    }
+    let mut session = open_session(directory_path)?;
+    session.format = format;
+    let prompt = arguments.prompt.or_else(|| directory.prompt.clone()).unwrap_or_else(|| DEFAULT_PROMPT.to_string());
+    if let Err(error) = install_interrupt_handler() {
+        eprintln!("Could not catch Ctrl-C, it will end the program: {}", error);
+    }
+    let result = run_repl(&mut session, &prompt).map_err(|error| Error::Io {
+        action: "read the command".to_string(),
+        source: io::Error::other(error),
+    });
+
+    // Leaving cleanly is a good moment to fold the journal into the save file.
+    // Whoever is logged in doesn't matter, see `access`.
+    if let Err(error) = session.compact() {
+        eprintln!("Could not compact {}: {}", directory_path.display(), error);
+    }
+    result
+}
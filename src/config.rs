@@ -0,0 +1,210 @@
+// Settings for the `untitled` programs.
+//
+// Every setting can come from four places. The first one that has it wins:
+//
+// 1. a command line flag
+// 2. an environment variable
+// 3. the config file
+// 4. the built-in default
+//
+// The config file is `$XDG_CONFIG_HOME/untitled/config.toml`, or
+// `~/.config/untitled/config.toml` without XDG_CONFIG_HOME. `--config <FILE>`
+// or UNTITLED_CONFIG read another file instead. A missing default file is
+// the same as an empty one, a missing file that was asked for is an error.
+// Everything in it is optional:
+//
+// [directory]
+// data-file = "/home/sally/company_directory.json"   # UNTITLED_DATA_FILE, --data-file
+// prompt = "{user}@directory> "                      # UNTITLED_PROMPT, --prompt
+// format = "json"                                    # UNTITLED_FORMAT, --format
+//
+// [piglatin]
+// dialect = "way"                                    # UNTITLED_PIG_LATIN_DIALECT, --dialect
+//
+// [guess]
+// low = 1                                            # UNTITLED_GUESS_LOW, --low
+// high = 100                                         # UNTITLED_GUESS_HIGH, --high
+//
+// A program only reads the sections it uses, so a mistake in one section
+// (or its environment variables) only stops the programs that read it.
+//
+// The users file, the refusal log and the audit log are kept next to the
+// data file. In the prompt `{user}` is replaced with whoever is logged in.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::chapter_8::common_collections::Dialect;
+use crate::chapter_8::output::OutputFormat;
+use crate::error::Error;
+
+pub const CONFIG_ENV: &str = "UNTITLED_CONFIG";
+pub const DATA_FILE_ENV: &str = "UNTITLED_DATA_FILE";
+pub const PROMPT_ENV: &str = "UNTITLED_PROMPT";
+pub const FORMAT_ENV: &str = "UNTITLED_FORMAT";
+pub const DIALECT_ENV: &str = "UNTITLED_PIG_LATIN_DIALECT";
+pub const GUESS_LOW_ENV: &str = "UNTITLED_GUESS_LOW";
+pub const GUESS_HIGH_ENV: &str = "UNTITLED_GUESS_HIGH";
+
+// The config file, read but not yet checked. Each section is only checked
+// when a program asks for it with `section`, so a bad directory setting
+// doesn't stop the guessing game, and Fibonacci doesn't look at any of it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    // Where the file was, for errors.
+    source: String,
+    sections: toml::Table,
+}
+
+// One `[section]` of the config file with the environment variables that
+// override it.
+pub trait Section: DeserializeOwned + Default {
+    const NAME: &'static str;
+
+    // Overrides whatever `var` has a value for. It is passed in so the
+    // environment can be faked.
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), Error>;
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DirectoryConfig {
+    pub data_file: Option<PathBuf>,
+    pub prompt: Option<String>,
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PigLatinConfig {
+    pub dialect: Option<Dialect>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuessConfig {
+    pub low: Option<u32>,
+    pub high: Option<u32>,
+}
+
+const SECTIONS: [&str; 3] = [DirectoryConfig::NAME, PigLatinConfig::NAME, GuessConfig::NAME];
+
+impl Config {
+    // The config file, `path` if one was given on the command line. The
+    // environment and the flags come on top of a section, see `section`.
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let requested = path.map(Path::to_path_buf).or_else(|| env::var_os(CONFIG_ENV).map(PathBuf::from));
+        match requested {
+            Some(path) => Config::read(&path),
+            None => match default_config_path() {
+                Some(path) if path.exists() => Config::read(&path),
+                _ => Ok(Config::default()),
+            },
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Config, Error> {
+        let text = fs::read_to_string(path).map_err(|error| Error::file("read", path, error))?;
+        let mut config = Config::parse(&text).map_err(|problem| Error::Parse {
+            input: path.display().to_string(),
+            problem,
+        })?;
+        config.source = path.display().to_string();
+        Ok(config)
+    }
+
+    // Only checks that `text` is TOML and has no sections nobody reads.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let sections: toml::Table = toml::from_str(text).map_err(|error| error.message().to_string())?;
+        if let Some(unknown) = sections.keys().find(|name| !SECTIONS.contains(&name.as_str())) {
+            return Err(format!("there is no [{}] section, only [{}]", unknown, SECTIONS.join("], [")));
+        }
+        Ok(Config {
+            source: "the config file".to_string(),
+            sections,
+        })
+    }
+
+    // The section as the file has it, with the environment variables on top.
+    // Flags are up to the caller.
+    pub fn section<S: Section>(&self) -> Result<S, Error> {
+        let mut section: S = self.file_section()?;
+        section.apply_env(|name| env::var(name).ok())?;
+        Ok(section)
+    }
+
+    // The section as the file has it, empty if it isn't there.
+    pub fn file_section<S: Section>(&self) -> Result<S, Error> {
+        match self.sections.get(S::NAME) {
+            Some(value) => value.clone().try_into().map_err(|error: toml::de::Error| Error::Parse {
+                input: format!("[{}] in {}", S::NAME, self.source),
+                problem: error.message().to_string(),
+            }),
+            None => Ok(S::default()),
+        }
+    }
+}
+
+impl Section for DirectoryConfig {
+    const NAME: &'static str = "directory";
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), Error> {
+        if let Some(data_file) = var(DATA_FILE_ENV) {
+            self.data_file = Some(PathBuf::from(data_file));
+        }
+        if let Some(prompt) = var(PROMPT_ENV) {
+            self.prompt = Some(prompt);
+        }
+        if let Some(format) = var(FORMAT_ENV) {
+            let format = OutputFormat::from_name(&format).ok_or_else(|| bad_env(FORMAT_ENV, &format, "text, json or tsv"))?;
+            self.format = Some(format);
+        }
+        Ok(())
+    }
+}
+
+impl Section for PigLatinConfig {
+    const NAME: &'static str = "piglatin";
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), Error> {
+        if let Some(dialect) = var(DIALECT_ENV) {
+            let dialect = Dialect::from_name(&dialect).ok_or_else(|| bad_env(DIALECT_ENV, &dialect, "yay, way, hay or ay"))?;
+            self.dialect = Some(dialect);
+        }
+        Ok(())
+    }
+}
+
+impl Section for GuessConfig {
+    const NAME: &'static str = "guess";
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), Error> {
+        if let Some(low) = var(GUESS_LOW_ENV) {
+            self.low = Some(low.trim().parse().map_err(|_| bad_env(GUESS_LOW_ENV, &low, "a whole number"))?);
+        }
+        if let Some(high) = var(GUESS_HIGH_ENV) {
+            self.high = Some(high.trim().parse().map_err(|_| bad_env(GUESS_HIGH_ENV, &high, "a whole number"))?);
+        }
+        Ok(())
+    }
+}
+
+// Where the config file is looked for when nobody said where it is.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("untitled").join("config.toml"))
+}
+
+fn bad_env(name: &str, value: &str, expected: &str) -> Error {
+    Error::Parse {
+        input: name.to_string(),
+        problem: format!("`{}` should be {}", value, expected),
+    }
+}
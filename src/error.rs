@@ -0,0 +1,96 @@
+// The one error type for everything outside the department commands.
+//
+// The department commands have their own errors with codes (see
+// `CommandError`), because scripts and the HTTP API hand those codes on.
+// They still fit in here, so a caller that does a bit of everything only
+// has one type to deal with.
+//
+// Every kind of failure has its own exit code, so scripts calling the
+// binaries can tell them apart:
+//
+// - 1: department commands failed
+// - 2: an argument is out of range (clap uses 2 for bad arguments too)
+// - 3: there was nothing to work on
+// - 4: something named doesn't exist
+// - 5: reading or writing failed
+// - 6: a file or some input couldn't be understood
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::chapter_8::common_collections::CommandError;
+
+#[derive(Debug)]
+pub enum Error {
+    // `action` is what was being done, like "read s.txt".
+    Io { action: String, source: io::Error },
+    // `input` names what couldn't be understood, like a file, and `problem`
+    // says why.
+    Parse { input: String, problem: String },
+    // There was nothing to work on, `what` says what was missing.
+    EmptyInput(&'static str),
+    NotFound(String),
+    OutOfRange { what: &'static str, value: String, allowed: String },
+    Command(CommandError),
+    ScriptFailed { failed: usize, commands: usize },
+}
+
+impl Error {
+    // An error from doing `action` to the file at `path`. A file that isn't
+    // there is told apart from one that couldn't be read.
+    pub fn file(action: &str, path: &Path, source: io::Error) -> Error {
+        match source.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(path.display().to_string()),
+            io::ErrorKind::InvalidData => Error::Parse {
+                input: path.display().to_string(),
+                problem: source.to_string(),
+            },
+            _ => Error::Io {
+                action: format!("{} {}", action, path.display()),
+                source,
+            },
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Command(_) | Error::ScriptFailed { .. } => 1,
+            Error::OutOfRange { .. } => 2,
+            Error::EmptyInput(_) => 3,
+            Error::NotFound(_) => 4,
+            Error::Io { .. } => 5,
+            Error::Parse { .. } => 6,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { action, source } => write!(f, "could not {}: {}", action, source),
+            Error::Parse { input, problem } => write!(f, "could not understand {}: {}", input, problem),
+            Error::EmptyInput(what) => write!(f, "no {} were given", what),
+            Error::NotFound(what) => write!(f, "{} does not exist", what),
+            Error::OutOfRange { what, value, allowed } => write!(f, "{} can't be {}, it must be {}", what, value, allowed),
+            Error::Command(error) => write!(f, "{}", error),
+            Error::ScriptFailed { failed, commands } => write!(f, "{} of {} commands failed", failed, commands),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Command(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<CommandError> for Error {
+    fn from(error: CommandError) -> Self {
+        Error::Command(error)
+    }
+}
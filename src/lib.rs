@@ -0,0 +1,59 @@
+// The programs from working through the Rust book, as a library.
+//
+// The chapters are where the code grew up and stay private. What other
+// crates (and the binaries in `src/bin`) get to use is the modules below,
+// which only re-export the parts that are meant to be used from outside:
+//
+// - `stats`: median and mode of a list of integers.
+// - `text`: pig latin.
+// - `directory`: the company directory, its commands, storage, access
+//   control, audit log, scripts, prompt and HTTP API.
+// - `demos`: the guessing game, Fibonacci and the temperature converter.
+// - `cli`: the command line of the binaries.
+// - `config`: their settings, from flags, the environment and a config file.
+//
+// Whatever can fail returns `Error` (or, for single department commands,
+// `CommandError`, which converts into it).
+//
+// Moving things around inside the chapters shouldn't break anybody, changing
+// what these modules export should.
+
+mod chapter_2;
+mod chapter_3;
+mod chapter_8;
+mod error;
+
+pub use error::Error;
+
+pub mod cli;
+pub mod config;
+
+pub mod stats {
+    pub use crate::chapter_8::common_collections::{MedianResult, _find_mode as find_mode, find_median};
+}
+
+pub mod text {
+    pub use crate::chapter_8::common_collections::{pig_latin_conversion, pig_latin_in_dialect, Dialect};
+}
+
+pub mod directory {
+    pub use crate::chapter_8::access::{Access, AccessError, Role, User, DEFAULT_DENIAL_LOG_PATH, DEFAULT_USERS_PATH};
+    pub use crate::chapter_8::audit::{AuditLog, AuditRecord, AuditState, DepartmentState, DEFAULT_AUDIT_LOG_PATH};
+    pub use crate::chapter_8::command::{parse_command, quote, Column, Command, ParseError, COMMAND_HELP};
+    pub use crate::chapter_8::common_collections::{execute, handle_command, run_command, run_line, CommandError, Outcome};
+    pub use crate::chapter_8::directory::{Department, Directory, DirectoryError, Employee, EmployeeId, Field};
+    pub use crate::chapter_8::output::{render_error, Output, OutputFormat};
+    pub use crate::chapter_8::persistence::DEFAULT_DIRECTORY_PATH;
+    pub use crate::chapter_8::repl::{install_interrupt_handler, run_repl, DEFAULT_PROMPT};
+    pub use crate::chapter_8::script::{run_script, ScriptSummary};
+    pub use crate::chapter_8::search::MatchKind;
+    pub use crate::chapter_8::server::ApiServer;
+    pub use crate::chapter_8::session::{Recovery, Session};
+    pub use crate::chapter_8::store::DirectoryStore;
+}
+
+pub mod demos {
+    pub use crate::chapter_2::guessing_game::play as guessing_game;
+    pub use crate::chapter_3::fibonacci::{fibonacci, FIBONACCI_MAX};
+    pub use crate::chapter_3::temperature_converter::convert_temperature;
+}
@@ -0,0 +1,152 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use untitled::config::{
+    Config, DirectoryConfig, GuessConfig, PigLatinConfig, Section, DATA_FILE_ENV, DIALECT_ENV, FORMAT_ENV, GUESS_LOW_ENV,
+};
+use untitled::directory::OutputFormat;
+use untitled::text::Dialect;
+use untitled::Error;
+
+const CONFIG: &str = r#"
+[directory]
+data-file = "/srv/company_directory.json"
+prompt = "{user}> "
+format = "json"
+
+[piglatin]
+dialect = "way"
+
+[guess]
+low = 1
+high = 100
+"#;
+
+#[test]
+fn every_setting_can_be_in_the_file() {
+    let config = Config::parse(CONFIG).unwrap();
+    let directory: DirectoryConfig = config.file_section().unwrap();
+    assert_eq!(directory.data_file, Some(PathBuf::from("/srv/company_directory.json")));
+    assert_eq!(directory.prompt.as_deref(), Some("{user}> "));
+    assert_eq!(directory.format, Some(OutputFormat::Json));
+    assert_eq!(config.file_section::<PigLatinConfig>().unwrap().dialect, Some(Dialect::Way));
+    let guess: GuessConfig = config.file_section().unwrap();
+    assert_eq!((guess.low, guess.high), (Some(1), Some(100)));
+}
+
+#[test]
+fn an_empty_file_sets_nothing() {
+    let config = Config::parse("").unwrap();
+    assert_eq!(config.file_section::<DirectoryConfig>().unwrap(), DirectoryConfig::default());
+    assert_eq!(config.file_section::<PigLatinConfig>().unwrap(), PigLatinConfig::default());
+    assert_eq!(config.file_section::<GuessConfig>().unwrap(), GuessConfig::default());
+}
+
+#[test]
+fn typos_are_errors() {
+    assert!(Config::parse("[guesses]\nlow = 3\n").is_err());
+    let config = Config::parse("[guess]\nlowest = 3\n[piglatin]\ndialect = \"klingon\"\n").unwrap();
+    assert!(config.file_section::<GuessConfig>().is_err());
+    assert!(config.file_section::<PigLatinConfig>().is_err());
+}
+
+#[test]
+fn a_bad_section_only_matters_to_whoever_reads_it() {
+    let config = Config::parse("[directory]\nformat = \"bogus\"\n\n[guess]\nlow = 3\n").unwrap();
+    let error = config.file_section::<DirectoryConfig>().unwrap_err();
+    assert!(error.to_string().contains("[directory]"), "{}", error);
+    assert_eq!(config.file_section::<GuessConfig>().unwrap().low, Some(3));
+}
+
+#[test]
+fn the_environment_beats_the_file() {
+    let config = Config::parse(CONFIG).unwrap();
+    let var = |name: &str| match name {
+        DATA_FILE_ENV => Some("here.json".to_string()),
+        DIALECT_ENV => Some("HAY".to_string()),
+        _ => None,
+    };
+    let mut directory: DirectoryConfig = config.file_section().unwrap();
+    directory.apply_env(var).unwrap();
+    let mut piglatin: PigLatinConfig = config.file_section().unwrap();
+    piglatin.apply_env(var).unwrap();
+    let mut guess: GuessConfig = config.file_section().unwrap();
+    guess.apply_env(var).unwrap();
+    assert_eq!(directory.data_file, Some(PathBuf::from("here.json")));
+    assert_eq!(piglatin.dialect, Some(Dialect::Hay));
+    // Whatever the environment doesn't mention stays as the file has it.
+    assert_eq!(directory.format, Some(OutputFormat::Json));
+    assert_eq!(guess.high, Some(100));
+}
+
+#[test]
+fn bad_environment_values_are_errors() {
+    let mut guess = GuessConfig::default();
+    let error = guess.apply_env(|name| (name == GUESS_LOW_ENV).then(|| "lots".to_string())).unwrap_err();
+    assert!(matches!(error, Error::Parse { .. }));
+    assert!(error.to_string().contains(GUESS_LOW_ENV));
+}
+
+#[test]
+fn a_bad_directory_setting_does_not_stop_the_other_programs() {
+    let dir = tempfile::tempdir().unwrap();
+    let untitled = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_untitled"))
+            .args(args)
+            .current_dir(dir.path())
+            .env("HOME", dir.path())
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("UNTITLED_CONFIG")
+            .env(FORMAT_ENV, "bogus")
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+    let fib = untitled(&["fib", "5"]);
+    assert!(fib.status.success(), "{}", String::from_utf8_lossy(&fib.stderr));
+    assert_eq!(String::from_utf8_lossy(&fib.stdout).trim(), "5");
+    assert_eq!(untitled(&["directory"]).status.code(), Some(6));
+}
+
+#[test]
+fn a_config_file_that_was_asked_for_must_exist() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    assert!(matches!(Config::read(&path), Err(Error::NotFound(_))));
+    fs::write(&path, CONFIG).unwrap();
+    let guess: GuessConfig = Config::read(&path).unwrap().file_section().unwrap();
+    assert_eq!(guess.high, Some(100));
+}
+
+#[test]
+fn the_guessing_game_reads_the_config_it_is_given() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("game.toml");
+    fs::write(&path, "[guess]\nlow = 5\nhigh = 5\n").unwrap();
+    let mut game = Command::new(env!("CARGO_BIN_EXE_guessing_game"))
+        .arg("--config")
+        .arg(&path)
+        .env("HOME", dir.path())
+        .env_remove("UNTITLED_CONFIG")
+        .env_remove(GUESS_LOW_ENV)
+        .env_remove("UNTITLED_GUESS_HIGH")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    game.stdin.take().unwrap().write_all(b"5\n5\n").unwrap();
+    let output = game.wait_with_output().unwrap();
+    assert!(output.status.success());
+    // Only 5 can come up, so guessing 5 wins both rounds.
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("You win!") && stdout.contains("You win.\nThis time..."), "{}", stdout);
+
+    let missing = Command::new(env!("CARGO_BIN_EXE_guessing_game"))
+        .args(["--config", "missing.toml"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(missing.status.code(), Some(4));
+}
@@ -0,0 +1,55 @@
+use std::process::Command;
+
+use untitled::demos::{convert_temperature, fibonacci, guessing_game, FIBONACCI_MAX};
+use untitled::Error;
+
+#[test]
+fn fibonacci_counts_one_one_two() {
+    let numbers: Vec<usize> = (1..=6).map(|n| fibonacci(n).unwrap()).collect();
+    assert_eq!(numbers, [1, 1, 2, 3, 5, 8]);
+    assert_eq!(fibonacci(FIBONACCI_MAX).unwrap(), 12200160415121876738);
+}
+
+#[test]
+fn fibonacci_past_64_bits_is_out_of_range() {
+    let error = fibonacci(FIBONACCI_MAX + 1).unwrap_err();
+    assert!(matches!(error, Error::OutOfRange { .. }));
+    assert_eq!(error.exit_code(), 2);
+}
+
+#[test]
+fn temperatures_convert_both_ways() {
+    let mut temperature = 212.0;
+    convert_temperature(&mut temperature, true).unwrap();
+    assert_eq!(temperature, 100.0);
+    convert_temperature(&mut temperature, false).unwrap();
+    assert_eq!(temperature, 212.0);
+}
+
+#[test]
+fn nothing_is_colder_than_absolute_zero() {
+    let mut temperature = -300.0;
+    assert!(matches!(convert_temperature(&mut temperature, false), Err(Error::OutOfRange { .. })));
+    assert_eq!(temperature, -300.0);
+    assert!(convert_temperature(&mut temperature, true).is_ok());
+}
+
+#[test]
+fn infinite_temperatures_are_out_of_range() {
+    for mut temperature in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+        let error = convert_temperature(&mut temperature, false).unwrap_err();
+        assert!(matches!(error, Error::OutOfRange { .. }));
+        assert_eq!(error.exit_code(), 2);
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_untitled")).args(["convert-temp", "--from", "c", "inf"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't be inf°C"));
+}
+
+#[test]
+fn the_guessing_range_must_not_be_empty() {
+    let error = guessing_game(10, 1).unwrap_err();
+    assert_eq!(error.to_string(), "the lowest number can't be 10, it must be at most the highest number, 1");
+}
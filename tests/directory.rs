@@ -0,0 +1,319 @@
+use std::fs;
+use std::io::Cursor;
+
+use untitled::directory::{
+    parse_command, quote, run_line, run_script, Access, AuditLog, Command, CommandError, Directory, Output, OutputFormat, Role, Session,
+};
+
+fn run(session: &mut Session, line: &str) -> Output {
+    match run_line(line, session) {
+        Ok((_, output)) => output,
+        Err(error) => panic!("`{}` failed: {}", line, error),
+    }
+}
+
+fn members(session: &Session, department: &str) -> Vec<String> {
+    let directory = &session.directory;
+    let department = directory.department(department).expect("no such department");
+    department.members.iter().map(|id| directory.name_of(*id)).collect()
+}
+
+#[test]
+fn commands_change_the_directory_and_undo_takes_them_back() {
+    let mut session = Session::new(Directory::new());
+    run(&mut session, "add Sally to Engineering");
+    run(&mut session, "add Amir to Sales");
+    run(&mut session, "move Amir from Sales to Engineering");
+    assert_eq!(members(&session, "Engineering"), ["Sally", "Amir"]);
+    assert!(members(&session, "Sales").is_empty());
+
+    run(&mut session, "undo");
+    assert_eq!(members(&session, "Engineering"), ["Sally"]);
+    assert_eq!(members(&session, "Sales"), ["Amir"]);
+}
+
+#[test]
+fn head_counts_after_adding_and_removing_agree_with_the_count() {
+    let mut session = Session::new(Directory::new());
+    run(&mut session, "create-department Sales");
+    let output = run(&mut session, "add Sally to Sales");
+    assert!(output.render(OutputFormat::Text).contains(&"Added Sally to the Sales department (1 person now)".to_string()));
+    let output = run(&mut session, "add Amir to Sales");
+    assert!(output.render(OutputFormat::Text).contains(&"Added Amir to the Sales department (2 people now)".to_string()));
+
+    let output = run(&mut session, "remove Sally from Sales");
+    assert_eq!(output.render(OutputFormat::Text), ["Removed Sally from the Sales department (1 person left in it)"]);
+    let output = run(&mut session, "remove Amir from Sales");
+    assert_eq!(output.render(OutputFormat::Text), ["Removed Amir from the Sales department (0 people left in it)"]);
+}
+
+#[test]
+fn listings_render_as_json_records() {
+    let mut session = Session::new(Directory::new());
+    run(&mut session, "add Sally to Engineering");
+    let output = run(&mut session, "list Engineering");
+    let rendered = output.render(OutputFormat::Json);
+    let value: serde_json::Value = serde_json::from_str(&rendered[0]).unwrap();
+    assert_eq!(value["records"][0]["name"], "Sally");
+    assert_eq!(value["records"][0]["departments"][0], "Engineering");
+}
+
+#[test]
+fn failures_come_back_as_errors_with_codes() {
+    let mut session = Session::new(Directory::new());
+    let error = run_line("remove Bob from Sales", &mut session).unwrap_err();
+    assert_eq!(error.code(), "no_such_employee");
+    assert!(matches!(run_line("frob", &mut session), Err(CommandError::Parse(_))));
+    assert_eq!(run_line("undo", &mut session).unwrap_err().code(), "nothing_to_undo");
+}
+
+#[test]
+fn commands_print_back_as_they_parse() {
+    for line in ["add \"Mary Ann\" to Sales", "move Amir from Sales to Engineering", "list Sales recursive"] {
+        let command = parse_command(line).unwrap();
+        assert_eq!(parse_command(&command.to_string()).unwrap(), command);
+    }
+    assert_eq!(parse_command("exit").unwrap(), Command::Quit);
+}
+
+#[test]
+fn the_org_chart_shows_the_tree_with_head_counts() {
+    let mut session = Session::new(Directory::new());
+    run(&mut session, "create-department Company");
+    run(&mut session, "create-department Engineering under Company");
+    run(&mut session, "create-department Platform under Engineering");
+    run(&mut session, "create-department Sales under Company");
+    run(&mut session, "add Sally to Company");
+    run(&mut session, "add Amir to Platform");
+    run(&mut session, "add Bo to Sales");
+    run(&mut session, "add Bea to Sales");
+
+    let output = run(&mut session, "org-chart");
+    assert_eq!(
+        output.render(OutputFormat::Text),
+        [
+            "Company (1 person, 4 in total)",
+            "|-- Engineering (0 people, 1 in total)",
+            "|   `-- Platform (1 person)",
+            "`-- Sales (2 people)",
+        ]
+    );
+    let value: serde_json::Value = serde_json::from_str(&output.render(OutputFormat::Json)[0]).unwrap();
+    assert_eq!(value["records"][2]["name"], "Platform");
+    assert_eq!(value["records"][2]["parent"], "Engineering");
+    assert_eq!(value["records"][0]["total"], 4);
+}
+
+#[test]
+fn a_department_cannot_move_under_its_own_branch() {
+    let mut session = Session::new(Directory::new());
+    run(&mut session, "create-department Company");
+    run(&mut session, "create-department Engineering under Company");
+    run(&mut session, "create-department Platform under Engineering");
+
+    assert_eq!(run_line("reparent Company under Platform", &mut session).unwrap_err().code(), "department_cycle");
+    assert_eq!(run_line("reparent Engineering under Engineering", &mut session).unwrap_err().code(), "department_cycle");
+    let output = run(&mut session, "org-chart");
+    assert_eq!(output.render(OutputFormat::Text)[0], "Company (0 people, 0 in total)");
+
+    run(&mut session, "reparent Platform");
+    run(&mut session, "reparent Company under Platform");
+    assert_eq!(run(&mut session, "org-chart").render(OutputFormat::Text)[0], "Platform (0 people, 0 in total)");
+}
+
+#[test]
+fn a_saved_directory_opens_where_it_left_off() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("company_directory.json");
+    {
+        let (mut session, _) = Session::open(&path).unwrap();
+        run(&mut session, "add Sally to Engineering");
+        run(&mut session, "add Amir to Engineering");
+    }
+    let (session, recovery) = Session::open(&path).unwrap();
+    assert_eq!(recovery.replayed, 2);
+    assert_eq!(members(&session, "Engineering"), ["Sally", "Amir"]);
+}
+
+#[test]
+fn a_change_cut_off_by_a_crash_is_dropped_on_opening() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("company_directory.json");
+    {
+        let (mut session, _) = Session::open(&path).unwrap();
+        run(&mut session, "add Sally to Engineering");
+        run(&mut session, "add Amir to Engineering");
+    }
+    let journal = dir.path().join("company_directory.json.journal");
+    let contents = fs::read(&journal).unwrap();
+    fs::write(&journal, &contents[..contents.len() - 5]).unwrap();
+
+    let (session, recovery) = Session::open(&path).unwrap();
+    assert!(recovery.truncated);
+    assert_eq!(recovery.replayed, 1);
+    assert_eq!(members(&session, "Engineering"), ["Sally"]);
+}
+
+#[test]
+fn scripts_count_the_commands_that_failed() {
+    let mut session = Session::new(Directory::new());
+    session.format = OutputFormat::Json;
+    let script = "# a comment\nadd Sally to Engineering\n\nremove Bob from Sales\nlist Engineering\n";
+    let summary = run_script("test", Cursor::new(script), false, &mut session).unwrap();
+    assert_eq!((summary.commands, summary.failed), (3, 1));
+
+    let summary = run_script("test", Cursor::new(script), true, &mut session).unwrap();
+    assert_eq!((summary.commands, summary.failed), (1, 1));
+}
+
+#[test]
+fn imports_tell_rows_left_out_from_rows_missing_a_manager() {
+    let dir = tempfile::tempdir().unwrap();
+    let roster = dir.path().join("roster.csv");
+    let roster_text = "name,department,manager\nSally,Sales,\nAmir,Sales,Nobody\n,Sales,Sally\nBo,Sales,Sally\n";
+    fs::write(&roster, roster_text).unwrap();
+
+    let mut session = Session::new(Directory::new());
+    let output = run(&mut session, &format!("import {}", quote(&roster.to_string_lossy())));
+    let lines = output.render(OutputFormat::Text);
+    assert!(lines.contains(&"1 row had problems and was left out:".to_string()), "{:?}", lines);
+    assert!(lines.contains(&"1 row was imported, but without its manager:".to_string()), "{:?}", lines);
+    assert!(lines.iter().any(|line| line.starts_with("  line 3:") && line.contains("Nobody")));
+    assert!(lines.iter().any(|line| line.starts_with("  line 4:")));
+    assert_eq!(members(&session, "Sales"), ["Sally", "Amir", "Bo"]);
+}
+
+#[test]
+fn an_exported_roster_imports_as_the_same_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let roster = dir.path().join("roster.csv");
+    let roster = quote(&roster.to_string_lossy());
+
+    let mut session = Session::new(Directory::new());
+    run(&mut session, "add \"Doe, Jane\" to Sales");
+    run(&mut session, "add \"Doe, Jane\" to Engineering");
+    run(&mut session, "add Amir to Engineering");
+    run(&mut session, "add Bo to Sales");
+    run(&mut session, "set Amir title \"Engineer, senior\"");
+    run(&mut session, "set Amir email amir@example.com");
+    run(&mut session, "set Amir start-date 2024-03-01");
+    // Amir's manager comes after Amir in the roster.
+    run(&mut session, "set Amir manager \"Doe, Jane\"");
+    run(&mut session, "set Bo manager Amir");
+    run(&mut session, &format!("export {}", roster));
+
+    let mut copy = Session::new(Directory::new());
+    run(&mut copy, &format!("import {}", roster));
+    for department in ["Sales", "Engineering"] {
+        assert_eq!(members(&copy, department).len(), members(&session, department).len());
+    }
+    let people = |session: &Session| {
+        let directory = &session.directory;
+        let mut people: Vec<_> = directory
+            .employees()
+            .map(|employee| {
+                let manager = employee.manager.map(|id| directory.name_of(id));
+                let departments = directory.departments_of(employee.id).into_iter().map(String::from).collect::<Vec<_>>();
+                (employee.name.clone(), employee.title.clone(), employee.email.clone(), employee.start_date.clone(), manager, departments)
+            })
+            .collect();
+        people.sort();
+        people
+    };
+    assert_eq!(people(&copy), people(&session));
+}
+
+#[test]
+fn people_with_the_same_name_are_not_exported() {
+    let dir = tempfile::tempdir().unwrap();
+    let roster = dir.path().join("roster.csv");
+    let mut session = Session::new(Directory::new());
+    run(&mut session, "add Sam to Sales");
+    run(&mut session, "add Pat to Engineering");
+    run(&mut session, "set Pat name Sam");
+    let error = run_line(&format!("export {}", quote(&roster.to_string_lossy())), &mut session).unwrap_err();
+    assert_eq!(error.code(), "shared_name");
+    assert!(!roster.exists());
+}
+
+#[test]
+fn roles_decide_who_may_change_the_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let users = dir.path().join("users.json");
+    let denials = dir.path().join("denials.log");
+    fs::write(&users, r#"{"users": {"Sally": "admin", "Bo": "viewer"}}"#).unwrap();
+
+    let mut session = Session::new(Directory::new());
+    session.access = Access::open(&users, &denials).unwrap();
+    assert_eq!(run_line("add Amir to Sales", &mut session).unwrap_err().code(), "permission_denied");
+
+    run(&mut session, "login Bo");
+    assert_eq!(session.access.role(), Role::Viewer);
+    assert!(run_line("add Amir to Sales", &mut session).is_err());
+    run(&mut session, "login Sally");
+    run(&mut session, "add Amir to Sales");
+    run(&mut session, "delete-department Sales");
+
+    let denied = fs::read_to_string(&denials).unwrap();
+    assert_eq!(denied.lines().count(), 2);
+    assert!(denied.contains(r#""user":"Bo""#));
+}
+
+#[test]
+fn undo_and_redo_need_the_role_of_the_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let users = dir.path().join("users.json");
+    let denials = dir.path().join("denials.log");
+    fs::write(&users, r#"{"users": {"Sally": "admin", "Amir": "editor"}}"#).unwrap();
+
+    let mut session = Session::new(Directory::new());
+    session.access = Access::open(&users, &denials).unwrap();
+    run(&mut session, "login Sally");
+    run(&mut session, "add Bo to Sales");
+    run(&mut session, "delete-department Sales");
+    run(&mut session, "undo");
+
+    run(&mut session, "login Amir");
+    assert_eq!(run_line("redo", &mut session).unwrap_err().code(), "permission_denied");
+    assert_eq!(members(&session, "Sales"), ["Bo"]);
+    // Undoing the add is an editor's business.
+    run(&mut session, "login Sally");
+    run(&mut session, "add Bea to Sales");
+    run(&mut session, "login Amir");
+    run(&mut session, "undo");
+    assert_eq!(members(&session, "Sales"), ["Bo"]);
+
+    let denied = fs::read_to_string(&denials).unwrap();
+    assert!(denied.contains(r#""command":"delete-department Sales""#), "{}", denied);
+}
+
+#[test]
+fn changes_are_audited_with_who_made_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = AuditLog::new(&dir.path().join("audit.log"));
+    let mut session = Session::new(Directory::new());
+    session.audit = Some(log.clone());
+    run(&mut session, "add Sally to Engineering");
+    run(&mut session, "list Engineering");
+    run(&mut session, "rename-department Engineering R&D");
+
+    let records = log.read().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].user, None);
+    assert!(records[0].mentions("sally"));
+    assert!(records[1].mentions("R&D"));
+    assert!(records[1].is_since("2000-01-01"));
+}
+
+#[test]
+fn an_unreadable_audit_log_is_reported_where_it_is() {
+    let dir = tempfile::tempdir().unwrap();
+    // A directory where the log should be can't be read as one.
+    let path = dir.path().join("data").join("audit.log");
+    fs::create_dir_all(&path).unwrap();
+    let mut session = Session::new(Directory::new());
+    session.audit = Some(AuditLog::new(&path));
+    let error = run_line("audit", &mut session).unwrap_err();
+    assert_eq!(error.code(), "file");
+    assert!(error.to_string().starts_with(&path.display().to_string()), "{}", error);
+}
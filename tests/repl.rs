@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use untitled::directory::{run_line, Session};
+
+// Runs the department prompt of the real binary on `input`, in `dir`, and
+// returns what it printed.
+fn prompt(dir: &Path, input: &str) -> String {
+    prompt_with(dir, &[], input)
+}
+
+fn prompt_with(dir: &Path, arguments: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_untitled"))
+        .args(["directory", "--data-file", "directory.json"])
+        .args(arguments)
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env_remove("UNTITLED_CONFIG")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn quitting_a_directory_on_disk_saves_without_asking() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = prompt(dir.path(), "add Sally to Sales\nquit\nn\nlist-all\n");
+    // Were it asked, the `n` would keep the prompt going.
+    assert!(!output.contains("Sally (#1)"));
+    assert_eq!(std::fs::read(dir.path().join("directory.json.journal")).unwrap(), b"");
+
+    let output = prompt(dir.path(), "list Sales\nquit\n");
+    assert!(output.contains("1. Sally"));
+}
+
+#[test]
+fn changes_left_in_the_journal_are_saved_on_the_way_out() {
+    let dir = tempfile::tempdir().unwrap();
+    // A session that ends without compacting leaves its changes in the journal.
+    let mut session = Session::open(&dir.path().join("directory.json")).unwrap().0;
+    run_line("add Amir to Sales", &mut session).unwrap();
+    drop(session);
+
+    prompt(dir.path(), "quit\n");
+    assert_eq!(std::fs::read(dir.path().join("directory.json.journal")).unwrap(), b"");
+    assert!(std::fs::read_to_string(dir.path().join("directory.json")).unwrap().contains("Amir"));
+}
+
+#[test]
+fn json_output_is_only_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = prompt_with(dir.path(), &["--format", "json"], "add Sally to Sales\nlist Sales\nquit\n");
+    for line in output.lines() {
+        assert!(serde_json::from_str::<serde_json::Value>(line).is_ok(), "not json: {}", line);
+    }
+    assert_eq!(output.lines().count(), 2);
+}
+
+#[test]
+fn leaving_compacts_the_journal_whoever_is_logged_in() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("users.json"), r#"{"users": {"Sally": "admin", "Bo": "viewer"}}"#).unwrap();
+    prompt(dir.path(), "login Sally\nadd Amir to Sales\nlogin Bo\ncompact\nquit\n");
+
+    // Bo may not compact, but leaving does it anyway.
+    let denials = std::fs::read_to_string(dir.path().join("denials.log")).unwrap();
+    assert!(denials.contains(r#""command":"compact""#));
+    assert_eq!(std::fs::read(dir.path().join("directory.json.journal")).unwrap(), b"");
+    assert!(std::fs::read_to_string(dir.path().join("directory.json")).unwrap().contains("Amir"));
+}
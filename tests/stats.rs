@@ -0,0 +1,44 @@
+use std::process::Command;
+
+use untitled::stats::{find_median, find_mode, MedianResult};
+use untitled::Error;
+
+#[test]
+fn median_of_an_odd_list_is_the_middle_number() {
+    assert_eq!(find_median(&[7, 1, 3]).unwrap(), MedianResult::Int(3));
+}
+
+#[test]
+fn median_of_an_even_list_is_the_middle_pair() {
+    assert_eq!(find_median(&[4, 1, 3, 2]).unwrap(), MedianResult::Float(2.5));
+}
+
+#[test]
+fn median_of_nothing_is_an_error() {
+    let error = find_median(&[]).unwrap_err();
+    assert!(matches!(error, Error::EmptyInput("numbers")));
+    assert_eq!(error.exit_code(), 3);
+}
+
+#[test]
+fn mode_is_the_most_common_number() {
+    assert_eq!(find_mode(&[1, 2, 2, 3, 2, 1]).unwrap(), 2);
+}
+
+#[test]
+fn mode_ties_go_to_the_smallest_number() {
+    assert_eq!(find_mode(&[5, 3, 5, 3]).unwrap(), 3);
+    assert!(matches!(find_mode(&[]), Err(Error::EmptyInput(_))));
+}
+
+#[test]
+fn the_stats_command_without_numbers_fails_with_its_own_exit_code() {
+    let output = Command::new(env!("CARGO_BIN_EXE_untitled")).arg("stats").output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "untitled: no numbers were given");
+    assert!(output.stdout.is_empty());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_untitled")).args(["stats", "3", "-1", "3"]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "The median for this list is 3\nThe mode of this list is 3\n");
+}
@@ -0,0 +1,28 @@
+use untitled::text::{pig_latin_conversion, pig_latin_in_dialect, Dialect};
+use untitled::Error;
+
+fn pig_latin(sentence: &str) -> String {
+    let mut words: Vec<String> = sentence.split_whitespace().map(String::from).collect();
+    pig_latin_conversion(&mut words).unwrap().join(" ")
+}
+
+#[test]
+fn consonants_move_to_the_end_with_ay() {
+    assert_eq!(pig_latin("first Hello"), "irstfay elloHay");
+}
+
+#[test]
+fn vowels_get_yay() {
+    assert_eq!(pig_latin("apple"), "appleyay");
+}
+
+#[test]
+fn nothing_to_convert_is_an_error() {
+    assert!(matches!(pig_latin_conversion(&mut Vec::new()), Err(Error::EmptyInput("words"))));
+}
+
+#[test]
+fn dialects_only_change_words_starting_with_a_vowel() {
+    let mut words = vec!["apple".to_string(), "first".to_string()];
+    assert_eq!(pig_latin_in_dialect(&mut words, Dialect::Way).unwrap(), &["appleway", "irstfay"]);
+}